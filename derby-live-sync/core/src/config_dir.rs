@@ -0,0 +1,158 @@
+//! Where the app keeps its own files: settings, the upload queue and the
+//! encrypted API key fallback. They used to live in the working directory,
+//! which changes with whichever shortcut launched the app.
+
+use log::{info, warn};
+use serde_json::{Map, Value};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+pub const ENV_VAR: &str = "DERBY_LIVE_SYNC_CONFIG_DIR";
+const APP_DIR: &str = "derby-live-sync";
+const PROFILES_DIR: &str = "profiles";
+
+/// Files written to the working directory by earlier versions
+const MIGRATED_FILES: &[&str] = &[
+    "settings.json",
+    "upload_queue.json",
+    "api_key.enc",
+    "api_key.key",
+];
+const OLD_SETTINGS_FIELDS: &[&str] = &["serverUrl", "databasePath", "eventKey"];
+
+static OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Uses `dir` for the rest of the process, as `--config-dir` asks. Only the
+/// first call has any effect.
+pub fn set_override(dir: PathBuf) {
+    if OVERRIDE.set(dir).is_err() {
+        warn!(target: "config_dir", "set_override: already set");
+    }
+}
+
+/// The override if one was given, then `DERBY_LIVE_SYNC_CONFIG_DIR`, then the
+/// platform config directory.
+pub fn path() -> PathBuf {
+    if let Some(dir) = OVERRIDE.get() {
+        return dir.clone();
+    }
+    if let Some(dir) = std::env::var_os(ENV_VAR).filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir);
+    }
+
+    dirs::config_dir()
+        .map(|dir| dir.join(APP_DIR))
+        .unwrap_or_else(|| std::env::current_dir().expect("Failed to get current directory"))
+}
+
+pub fn file(name: &str) -> PathBuf {
+    path().join(name)
+}
+
+/// Where a profile keeps its upload queue and API key fallback.
+pub fn profile_dir(profile: &str) -> PathBuf {
+    profile_dir_in(&path(), profile)
+}
+
+/// Profile names are typed by hand, so anything that could be a path
+/// separator or otherwise trouble in a file name is escaped as `%XX`.
+pub fn profile_dir_in(dir: &Path, profile: &str) -> PathBuf {
+    let mut escaped = String::new();
+    for byte in profile.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b' ' | b'-' | b'_' => {
+                escaped.push(byte as char)
+            }
+            _ => escaped.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    dir.join(PROFILES_DIR).join(escaped)
+}
+
+/// Copies files left in the working directory by earlier versions into the
+/// config directory, leaving the originals where they are. Files already in
+/// the config directory win, so this is a no-op after the first run.
+pub fn migrate_from_cwd() {
+    match std::env::current_dir() {
+        Ok(cwd) => migrate(&cwd, &path()),
+        Err(e) => warn!(target: "config_dir", "migrate_from_cwd: {}", e),
+    }
+}
+
+/// Only copies from a directory whose `settings.json` was written by an
+/// earlier version, so whatever else happens to be in the working directory
+/// is never mistaken for this app's files.
+pub fn migrate(from: &Path, to: &Path) {
+    if from == to || !has_old_settings(from) {
+        return;
+    }
+
+    for name in MIGRATED_FILES {
+        let old = from.join(name);
+        let new = to.join(name);
+        if !old.is_file() || new.exists() {
+            continue;
+        }
+
+        let copied = std::fs::create_dir_all(to).and_then(|_| std::fs::copy(&old, &new));
+        match copied {
+            Ok(_) => info!(target: "config_dir", "migrate: copied {:?} to {:?}", old, new),
+            Err(e) => {
+                warn!(target: "config_dir", "migrate: failed to copy {:?}: {}", old, e);
+                continue;
+            }
+        }
+
+        if *name == "settings.json" {
+            if let Err(e) = remove_api_key(&old) {
+                warn!(target: "config_dir", "migrate: failed to remove the API key from {:?}: {}", old, e);
+            }
+        }
+    }
+}
+
+/// The copy in the config directory is where the API key now gets moved to
+/// the keyring from, so the plain text one left behind in the working
+/// directory, perhaps on a shared laptop, is dropped.
+fn remove_api_key(path: &Path) -> std::io::Result<()> {
+    let contents = std::fs::read(path)?;
+    let mut settings: Map<String, Value> = serde_json::from_slice(&contents)?;
+    if settings.remove("apiKey").is_none() {
+        return Ok(());
+    }
+    write_atomic(path, &serde_json::to_vec_pretty(&settings)?)
+}
+
+/// Earlier versions kept these at the top level of `settings.json`
+fn has_old_settings(dir: &Path) -> bool {
+    std::fs::read(dir.join("settings.json"))
+        .ok()
+        .and_then(|contents| serde_json::from_slice::<Map<String, Value>>(&contents).ok())
+        .is_some_and(|settings| {
+            OLD_SETTINGS_FIELDS
+                .iter()
+                .any(|field| settings.contains_key(*field))
+        })
+}
+
+/// Writes to a temporary file beside `path` and renames it into place, so a
+/// crash mid-write leaves the old file rather than half of the new one.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    let mut file = std::fs::File::create(&temp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+
+    std::fs::rename(&temp_path, path)
+}
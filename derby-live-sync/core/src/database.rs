@@ -0,0 +1,549 @@
+extern crate notify;
+extern crate rusqlite;
+extern crate serde;
+
+use log::info;
+use rusqlite::{params, Connection, ErrorCode, OpenFlags};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::source::{RaceData, RaceDataSource, RaceStructure, SourceError, SourceKind};
+use crate::validate;
+
+// The timing software holds its write lock for the few milliseconds it takes
+// to save a heat, so a short wait is normally enough.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(2);
+const BUSY_RETRIES: u32 = 3;
+const BUSY_RETRY_DELAY: Duration = Duration::from_millis(250);
+// Far more lanes than any track has, and no more than `unused-lane-mask`, a
+// 64-bit integer, can describe.
+const MAX_LANES: i32 = 64;
+
+pub struct Client {
+    conn: Connection,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Racer {
+    pub racer_id: i32,
+    pub last_name: String,
+    pub first_name: String,
+    pub car_number: i32,
+    pub car_name: Option<String>,
+    pub group: String,
+    pub rank: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RacerHeat {
+    pub car_number: i32,
+    pub racer_id: i32,
+    pub heat_number: i32,
+    pub finish_seconds: Option<f64>,
+    pub finish_place: Option<i32>,
+    pub group: String,
+    pub lane_number: i32,
+    pub finished_at_unix: Option<i64>,
+    pub result_id: i32,
+    pub round_id: Option<i32>,
+    #[serde(default)]
+    pub status: HeatStatus,
+}
+
+/// Where a single car's run in a heat stands. Without this a heat that has
+/// not been raced and a car that never crossed the finish line look alike.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeatStatus {
+    #[default]
+    Scheduled,
+    Completed,
+    /// The run was thrown out and the heat is to be raced again
+    Rerun,
+    /// The heat was run but the timer never saw this car finish
+    Dnf,
+    /// The racer is excluded from the results
+    Dq,
+    /// The car raced, or is to race, with no other cars in the heat
+    Bye,
+}
+
+impl HeatStatus {
+    /// Works out a run's status from its result row. `ignore_time` and
+    /// `excluded` come from the timer's discarded-run and excluded-racer
+    /// flags, where the schema has them.
+    pub fn derive(racer_heat: &RacerHeat, ignore_time: bool, excluded: bool) -> HeatStatus {
+        if excluded {
+            return HeatStatus::Dq;
+        }
+        if ignore_time {
+            return HeatStatus::Rerun;
+        }
+
+        match racer_heat.finish_seconds {
+            Some(seconds) if validate::is_real_time(seconds) => HeatStatus::Completed,
+            Some(_) => HeatStatus::Dnf,
+            None if racer_heat.finished_at_unix.is_some() => HeatStatus::Dnf,
+            None => HeatStatus::Scheduled,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Class {
+    pub class_id: i32,
+    pub name: String,
+    pub sort_order: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rank {
+    pub rank_id: i32,
+    pub name: String,
+    pub class_id: Option<i32>,
+    pub sort_order: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundKind {
+    Preliminary,
+    Final,
+    GrandFinal,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Round {
+    pub round_id: i32,
+    pub class_id: i32,
+    pub round_number: i32,
+    pub kind: RoundKind,
+    pub label: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackConfig {
+    pub lane_count: i32,
+    pub unused_lanes: Vec<i32>,
+}
+
+impl Client {
+    pub fn new(path: PathBuf) -> Result<Client, rusqlite::Error> {
+        let conn = open_read_only(&path)?;
+        Ok(Client { conn })
+    }
+
+    pub fn select_racers(&self) -> Result<Vec<Racer>, rusqlite::Error> {
+        info!(target: "sync", "select_racers");
+        let mut stmt = self.conn.prepare(
+            "SELECT
+                  RacerID as 'racer_id',
+                  LastName as 'last_name',
+                  FirstName as 'first_name',
+                  CarNumber as 'car_number',
+                  CarName as 'car_name',
+                  Class as 'group',
+                  Rank as 'rank'
+                FROM qryRoster qr",
+        )?;
+        let racer_iter = stmt.query_map(params![], |row| {
+            Ok(Racer {
+                racer_id: row.get(0)?,
+                last_name: row.get(1)?,
+                first_name: row.get(2)?,
+                car_number: row.get(3)?,
+                car_name: row.get(4)?,
+                group: row.get(5)?,
+                rank: row.get(6)?,
+            })
+        })?;
+
+        let mut racers = Vec::new();
+        for racer in racer_iter {
+            racers.push(racer?);
+        }
+
+        Ok(racers)
+    }
+
+    pub fn select_racer_heats_with_times(&self) -> Result<Vec<RacerHeat>, rusqlite::Error> {
+        info!(target: "sync", "select_racer_heats_with_times");
+        // Optional columns are left unqualified so their fallbacks are plain
+        // SQL values; none of them appear in more than one of the tables.
+        let round_id = or_column(&self.conn, "RaceChart", "RoundID", "NULL")?;
+        let ignore_time = or_column(&self.conn, "RaceChart", "IgnoreTime", "0")?;
+        let exclude = or_column(&self.conn, "RegistrationInfo", "Exclude", "0")?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT
+                  ri.CarNumber as 'car_number',
+                  ri.RacerID as 'racer_id',
+                  rc.Heat as 'heat_number',
+                  rc.FinishTime as 'finish_seconds',
+                  rc.FinishPlace as 'finish_place',
+                  c.Class as 'group',
+                  rc.Lane as 'lane_number',
+                  CAST(STRFTIME('%s', rc.Completed) as bigint) as 'finished_at_unix',
+                  rc.ResultID as 'result_id',
+                  {} as 'round_id',
+                  COALESCE({}, 0) as 'ignore_time',
+                  COALESCE({}, 0) as 'excluded'
+                FROM RaceChart rc
+                INNER JOIN RegistrationInfo ri ON rc.RacerID = ri.RacerID
+                INNER JOIN Classes c ON c.ClassID = rc.ClassID
+                INNER JOIN Ranks rk ON rk.RankID = ri.RankID",
+            round_id, ignore_time, exclude
+        ))?;
+
+        let racer_heat_iter = stmt.query_map(params![], |row| {
+            Ok(RacerHeat {
+                car_number: row.get(0)?,
+                racer_id: row.get(1)?,
+                heat_number: row.get(2)?,
+                finish_seconds: row.get(3)?,
+                finish_place: row.get(4)?,
+                group: row.get(5)?,
+                lane_number: row.get(6)?,
+                finished_at_unix: row.get(7)?,
+                result_id: row.get(8)?,
+                round_id: row.get(9)?,
+                status: HeatStatus::Scheduled,
+            }
+            .with_status(row.get(10)?, row.get(11)?))
+        })?;
+
+        let mut racer_heats = Vec::new();
+        for racer_heat in racer_heat_iter {
+            racer_heats.push(racer_heat?);
+        }
+        mark_byes(&mut racer_heats);
+
+        Ok(racer_heats)
+    }
+}
+
+impl RaceDataSource for Client {
+    fn kind(&self) -> SourceKind {
+        SourceKind::GrandPrix
+    }
+
+    fn collect_data(&self) -> Result<RaceData, SourceError> {
+        info!(target: "sync", "collect_data");
+
+        let data = read_snapshot(&self.conn, || {
+            Ok(RaceData {
+                racers: self.select_racers()?,
+                racer_heats: self.select_racer_heats_with_times()?,
+                structure: select_structure(&self.conn)?,
+            })
+        })?;
+
+        Ok(data)
+    }
+}
+
+/// Reads the classes, ranks and track setup. GrandPrix and DerbyNet share
+/// these tables, differing only in whether sort orders and `RaceInfo` exist.
+pub fn select_structure(conn: &Connection) -> Result<RaceStructure, rusqlite::Error> {
+    Ok(RaceStructure {
+        classes: select_classes(conn)?,
+        ranks: select_ranks(conn)?,
+        track: select_track_config(conn)?,
+        rounds: select_rounds(conn)?,
+    })
+}
+
+fn select_classes(conn: &Connection) -> Result<Vec<Class>, rusqlite::Error> {
+    info!(target: "sync", "select_classes");
+    let sort_order = or_column(conn, "Classes", "SortOrder", "ClassID")?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT
+              ClassID as 'class_id',
+              Class as 'name',
+              COALESCE({}, ClassID) as 'sort_order'
+            FROM Classes
+            ORDER BY 3, 1",
+        sort_order
+    ))?;
+    let classes = stmt
+        .query_map(params![], |row| {
+            Ok(Class {
+                class_id: row.get(0)?,
+                name: row.get(1)?,
+                sort_order: row.get(2)?,
+            })
+        })?
+        .collect();
+
+    classes
+}
+
+fn select_ranks(conn: &Connection) -> Result<Vec<Rank>, rusqlite::Error> {
+    info!(target: "sync", "select_ranks");
+    let class_id = or_column(conn, "Ranks", "ClassID", "NULL")?;
+    let sort_order = or_column(conn, "Ranks", "SortOrder", "RankID")?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT
+              RankID as 'rank_id',
+              Rank as 'name',
+              {} as 'class_id',
+              COALESCE({}, RankID) as 'sort_order'
+            FROM Ranks
+            ORDER BY 3, 4, 1",
+        class_id, sort_order
+    ))?;
+    let ranks = stmt
+        .query_map(params![], |row| {
+            Ok(Rank {
+                rank_id: row.get(0)?,
+                name: row.get(1)?,
+                class_id: row.get(2)?,
+                sort_order: row.get(3)?,
+            })
+        })?
+        .collect();
+
+    ranks
+}
+
+/// Reads the rounds of racing. The first round of a class is its
+/// preliminaries and later rounds are finals; a round whose entrants are
+/// registered in more than one class draws from several dens, so it is
+/// treated as the grand final.
+fn select_rounds(conn: &Connection) -> Result<Vec<Round>, rusqlite::Error> {
+    info!(target: "sync", "select_rounds");
+    if !has_table(conn, "Rounds")? || !has_column(conn, "RaceChart", "RoundID")? {
+        return Ok(Vec::new());
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT
+              rd.RoundID as 'round_id',
+              rd.ClassID as 'class_id',
+              rd.Round as 'round_number',
+              c.Class as 'class_name',
+              (SELECT COUNT(DISTINCT ri.ClassID)
+                FROM RaceChart rc
+                INNER JOIN RegistrationInfo ri ON rc.RacerID = ri.RacerID
+                WHERE rc.RoundID = rd.RoundID) as 'entrant_classes'
+            FROM Rounds rd
+            INNER JOIN Classes c ON c.ClassID = rd.ClassID
+            ORDER BY rd.Round, rd.ClassID",
+    )?;
+    let rounds = stmt
+        .query_map(params![], |row| {
+            let round_number: i32 = row.get(2)?;
+            let class_name: String = row.get(3)?;
+            let entrant_classes: i32 = row.get(4)?;
+
+            let kind = if entrant_classes > 1 {
+                RoundKind::GrandFinal
+            } else if round_number > 1 {
+                RoundKind::Final
+            } else {
+                RoundKind::Preliminary
+            };
+            let label = match kind {
+                RoundKind::Preliminary => format!("{} Preliminaries", class_name),
+                RoundKind::Final | RoundKind::GrandFinal => format!("{} Finals", class_name),
+            };
+
+            Ok(Round {
+                round_id: row.get(0)?,
+                class_id: row.get(1)?,
+                round_number,
+                kind,
+                label,
+            })
+        })?
+        .collect();
+
+    rounds
+}
+
+/// Uses the lane settings in `RaceInfo` where the timer keeps them, and
+/// otherwise infers the track from the lanes used in the race chart, treating
+/// any lane that was never scheduled as unused.
+fn select_track_config(conn: &Connection) -> Result<TrackConfig, rusqlite::Error> {
+    info!(target: "sync", "select_track_config");
+    let mut stmt = conn.prepare("SELECT DISTINCT Lane FROM RaceChart ORDER BY Lane")?;
+    let charted_lanes = stmt
+        .query_map(params![], |row| row.get::<_, i32>(0))?
+        .collect::<Result<Vec<i32>, _>>()?;
+
+    let lane_count = race_info(conn, "lane_count")?
+        .and_then(|value| value.trim().parse().ok())
+        .or_else(|| charted_lanes.last().copied())
+        .unwrap_or_default()
+        .clamp(0, MAX_LANES);
+
+    let unused_lanes = match race_info(conn, "unused-lane-mask")?
+        .and_then(|value| value.trim().parse::<i64>().ok())
+    {
+        Some(mask) => (1..=lane_count)
+            .filter(|lane| {
+                1i64.checked_shl((lane - 1) as u32)
+                    .is_some_and(|bit| mask & bit != 0)
+            })
+            .collect(),
+        None => (1..=lane_count)
+            .filter(|lane| !charted_lanes.contains(lane))
+            .collect(),
+    };
+
+    Ok(TrackConfig {
+        lane_count,
+        unused_lanes,
+    })
+}
+
+fn race_info(conn: &Connection, key: &str) -> Result<Option<String>, rusqlite::Error> {
+    if !has_table(conn, "RaceInfo")? {
+        return Ok(None);
+    }
+
+    conn.query_row(
+        "SELECT ItemValue FROM RaceInfo WHERE ItemKey = ?1",
+        params![key],
+        |row| row.get::<_, Option<String>>(0),
+    )
+    .or_else(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => Ok(None),
+        e => Err(e),
+    })
+}
+
+pub fn has_table(conn: &Connection, name: &str) -> Result<bool, rusqlite::Error> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type IN ('table', 'view') AND name = ?1 COLLATE NOCASE",
+        params![name],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|count| count > 0)
+}
+
+pub fn table_columns(conn: &Connection, table: &str) -> Result<Vec<String>, rusqlite::Error> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?1)")?;
+    let columns = stmt
+        .query_map(params![table], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+
+    Ok(columns)
+}
+
+impl RacerHeat {
+    pub fn with_status(mut self, ignore_time: bool, excluded: bool) -> RacerHeat {
+        self.status = HeatStatus::derive(&self, ignore_time, excluded);
+        self
+    }
+}
+
+/// Marks runs in heats with only one car as byes. Runs that were thrown out
+/// or never finished keep that status, since it matters more to the display.
+pub fn mark_byes(racer_heats: &mut [RacerHeat]) {
+    let mut cars_per_heat: HashMap<(Option<i32>, String, i32), usize> = HashMap::new();
+    for racer_heat in racer_heats.iter() {
+        *cars_per_heat
+            .entry((
+                racer_heat.round_id,
+                racer_heat.group.clone(),
+                racer_heat.heat_number,
+            ))
+            .or_default() += 1;
+    }
+
+    for racer_heat in racer_heats.iter_mut() {
+        let key = (
+            racer_heat.round_id,
+            racer_heat.group.clone(),
+            racer_heat.heat_number,
+        );
+        let alone = cars_per_heat.get(&key) == Some(&1);
+        if alone
+            && matches!(
+                racer_heat.status,
+                HeatStatus::Scheduled | HeatStatus::Completed
+            )
+        {
+            racer_heat.status = HeatStatus::Bye;
+        }
+    }
+}
+
+/// `column` when `table` has it, otherwise the `fallback` expression, for
+/// columns that only some versions of the timing software have.
+pub fn or_column<'a>(
+    conn: &Connection,
+    table: &str,
+    column: &'a str,
+    fallback: &'a str,
+) -> Result<&'a str, rusqlite::Error> {
+    Ok(if has_column(conn, table, column)? {
+        column
+    } else {
+        fallback
+    })
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool, rusqlite::Error> {
+    Ok(table_columns(conn, table)?
+        .iter()
+        .any(|c| c.eq_ignore_ascii_case(column)))
+}
+
+/// Opens a timing database so that nothing this app does can write to it or
+/// take a lock that would stall the timing software mid-race.
+pub fn open_read_only(path: &Path) -> Result<Connection, rusqlite::Error> {
+    let conn = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    conn.pragma_update(None, "query_only", true)?;
+
+    Ok(conn)
+}
+
+/// Runs `query` again when it fails because the timing software is in the
+/// middle of writing, giving up after a few attempts.
+fn retry_when_busy<T>(
+    mut query: impl FnMut() -> Result<T, rusqlite::Error>,
+) -> Result<T, rusqlite::Error> {
+    let mut attempt = 0;
+    loop {
+        match query() {
+            Err(e) if is_busy(&e) && attempt < BUSY_RETRIES => {
+                attempt += 1;
+                info!(target: "sync", "retry_when_busy: attempt {} after {}", attempt, e);
+                std::thread::sleep(BUSY_RETRY_DELAY * attempt);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Runs every query in `read` inside one read transaction, so a heat saved
+/// part way through cannot leave heats pointing at racers that were not read.
+/// The whole snapshot is retried if the timing software is mid-write.
+pub fn read_snapshot<T>(
+    conn: &Connection,
+    mut read: impl FnMut() -> Result<T, rusqlite::Error>,
+) -> Result<T, rusqlite::Error> {
+    retry_when_busy(|| {
+        let tx = conn.unchecked_transaction()?;
+        let snapshot = read()?;
+        tx.finish()?;
+
+        Ok(snapshot)
+    })
+}
+
+fn is_busy(e: &rusqlite::Error) -> bool {
+    matches!(
+        e.sqlite_error_code(),
+        Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+    )
+}
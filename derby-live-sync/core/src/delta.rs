@@ -0,0 +1,142 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use crate::database::{Racer, RacerHeat};
+use crate::source::{RaceData, RaceStructure};
+use crate::standings::Standing;
+
+// Even when nothing looks different, everything is resent this often in case
+// the server missed an update or was reset.
+pub const FULL_RESYNC_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Delta {
+    pub full_resync: bool,
+    pub racers: Vec<Racer>,
+    pub racer_heats: Vec<RacerHeat>,
+    pub removed_racer_ids: Vec<i32>,
+    pub removed_result_ids: Vec<i32>,
+    pub structure: Option<RaceStructure>,
+    #[serde(default)]
+    pub standings: Option<Vec<Standing>>,
+}
+
+impl Delta {
+    pub fn is_empty(&self) -> bool {
+        self.structure.is_none()
+            && self.standings.is_none()
+            && self.racers.is_empty()
+            && self.racer_heats.is_empty()
+            && self.removed_racer_ids.is_empty()
+            && self.removed_result_ids.is_empty()
+    }
+}
+
+/// Remembers the rows last handed to the upload queue, keyed by `racer_id`
+/// and `result_id`, so that only added, changed or removed rows are sent.
+/// The structure and leaderboard are sent whole, and only when they change.
+#[derive(Debug, Default)]
+pub struct SyncLedger {
+    racers: HashMap<i32, Racer>,
+    racer_heats: HashMap<i32, RacerHeat>,
+    structure: Option<RaceStructure>,
+    standings: Option<Vec<Standing>>,
+    last_full_resync: Option<Instant>,
+}
+
+impl SyncLedger {
+    pub fn full_resync_due(&self) -> bool {
+        self.last_full_resync
+            .is_none_or(|at| at.elapsed() >= FULL_RESYNC_INTERVAL)
+    }
+
+    pub fn diff(&self, data: RaceData, standings: Vec<Standing>) -> Delta {
+        let RaceData {
+            racers,
+            racer_heats,
+            structure,
+        } = data;
+
+        let full_resync = self.full_resync_due();
+
+        let removed_racer_ids = removed_keys(&self.racers, racers.iter().map(|r| r.racer_id));
+        let removed_result_ids =
+            removed_keys(&self.racer_heats, racer_heats.iter().map(|rh| rh.result_id));
+
+        let (racers, racer_heats) = if full_resync {
+            (racers, racer_heats)
+        } else {
+            (
+                racers
+                    .into_iter()
+                    .filter(|r| self.racers.get(&r.racer_id) != Some(r))
+                    .collect(),
+                racer_heats
+                    .into_iter()
+                    .filter(|rh| self.racer_heats.get(&rh.result_id) != Some(rh))
+                    .collect(),
+            )
+        };
+
+        let structure =
+            (full_resync || self.structure.as_ref() != Some(&structure)).then_some(structure);
+        let standings =
+            (full_resync || self.standings.as_ref() != Some(&standings)).then_some(standings);
+
+        info!(target: "sync", "diff: full_resync:{}, structure:{}, standings:{}, racers:{}, racer_heats:{}, removed racers:{}, removed racer_heats:{}",
+            full_resync, structure.is_some(), standings.is_some(), racers.len(), racer_heats.len(), removed_racer_ids.len(), removed_result_ids.len());
+
+        Delta {
+            full_resync,
+            racers,
+            racer_heats,
+            removed_racer_ids,
+            removed_result_ids,
+            structure,
+            standings,
+        }
+    }
+
+    pub fn commit(&mut self, delta: &Delta) {
+        if delta.full_resync {
+            self.racers.clear();
+            self.racer_heats.clear();
+            self.last_full_resync = Some(Instant::now());
+        }
+
+        for racer in &delta.racers {
+            self.racers.insert(racer.racer_id, racer.clone());
+        }
+        for racer_heat in &delta.racer_heats {
+            self.racer_heats
+                .insert(racer_heat.result_id, racer_heat.clone());
+        }
+        for racer_id in &delta.removed_racer_ids {
+            self.racers.remove(racer_id);
+        }
+        for result_id in &delta.removed_result_ids {
+            self.racer_heats.remove(result_id);
+        }
+        if let Some(structure) = &delta.structure {
+            self.structure = Some(structure.clone());
+        }
+        if let Some(standings) = &delta.standings {
+            self.standings = Some(standings.clone());
+        }
+    }
+}
+
+fn removed_keys<T>(previous: &HashMap<i32, T>, current: impl Iterator<Item = i32>) -> Vec<i32> {
+    let current: HashSet<i32> = current.collect();
+    let mut removed: Vec<i32> = previous
+        .keys()
+        .filter(|key| !current.contains(key))
+        .copied()
+        .collect();
+    removed.sort_unstable();
+    removed
+}
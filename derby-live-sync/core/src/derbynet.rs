@@ -0,0 +1,128 @@
+extern crate rusqlite;
+
+use log::info;
+use rusqlite::{params, Connection};
+use std::path::PathBuf;
+
+use crate::database::{self, HeatStatus, Racer, RacerHeat};
+use crate::source::{RaceData, RaceDataSource, SourceError, SourceKind};
+
+/// Reads a DerbyNet database. DerbyNet keeps its roster in
+/// `RegistrationInfo` and numbers heats within each round, so `masterheat`
+/// is used where it has been assigned to give heats a single running order.
+pub struct Client {
+    conn: Connection,
+}
+
+impl Client {
+    pub fn new(path: PathBuf) -> Result<Client, rusqlite::Error> {
+        let conn = database::open_read_only(&path)?;
+        Ok(Client { conn })
+    }
+
+    pub fn select_racers(&self) -> Result<Vec<Racer>, rusqlite::Error> {
+        info!(target: "sync", "derbynet::select_racers");
+        let mut stmt = self.conn.prepare(
+            "SELECT
+                  r.racerid as 'racer_id',
+                  r.lastname as 'last_name',
+                  r.firstname as 'first_name',
+                  r.carnumber as 'car_number',
+                  r.carname as 'car_name',
+                  c.class as 'group',
+                  COALESCE(rk.rank, '') as 'rank'
+                FROM RegistrationInfo r
+                INNER JOIN Classes c ON c.classid = r.classid
+                LEFT JOIN Ranks rk ON rk.rankid = r.rankid",
+        )?;
+        let racer_iter = stmt.query_map(params![], |row| {
+            Ok(Racer {
+                racer_id: row.get(0)?,
+                last_name: row.get(1)?,
+                first_name: row.get(2)?,
+                car_number: row.get(3)?,
+                car_name: row.get(4)?,
+                group: row.get(5)?,
+                rank: row.get(6)?,
+            })
+        })?;
+
+        let mut racers = Vec::new();
+        for racer in racer_iter {
+            racers.push(racer?);
+        }
+
+        Ok(racers)
+    }
+
+    pub fn select_racer_heats_with_times(&self) -> Result<Vec<RacerHeat>, rusqlite::Error> {
+        info!(target: "sync", "derbynet::select_racer_heats_with_times");
+        let ignore_time = database::or_column(&self.conn, "RaceChart", "ignoretime", "0")?;
+        let exclude = database::or_column(&self.conn, "RegistrationInfo", "exclude", "0")?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT
+                  r.carnumber as 'car_number',
+                  r.racerid as 'racer_id',
+                  COALESCE(NULLIF(rc.masterheat, 0), rc.heat) as 'heat_number',
+                  rc.finishtime as 'finish_seconds',
+                  rc.finishplace as 'finish_place',
+                  c.class as 'group',
+                  rc.lane as 'lane_number',
+                  CAST(STRFTIME('%s', rc.completed) as bigint) as 'finished_at_unix',
+                  rc.resultid as 'result_id',
+                  rc.roundid as 'round_id',
+                  COALESCE({}, 0) as 'ignore_time',
+                  COALESCE({}, 0) as 'excluded'
+                FROM RaceChart rc
+                INNER JOIN RegistrationInfo r ON rc.racerid = r.racerid
+                INNER JOIN Rounds rd ON rd.roundid = rc.roundid
+                INNER JOIN Classes c ON c.classid = rd.classid",
+            ignore_time, exclude
+        ))?;
+
+        let racer_heat_iter = stmt.query_map(params![], |row| {
+            Ok(RacerHeat {
+                car_number: row.get(0)?,
+                racer_id: row.get(1)?,
+                heat_number: row.get(2)?,
+                finish_seconds: row.get(3)?,
+                finish_place: row.get(4)?,
+                group: row.get(5)?,
+                lane_number: row.get(6)?,
+                finished_at_unix: row.get(7)?,
+                result_id: row.get(8)?,
+                round_id: row.get(9)?,
+                status: HeatStatus::Scheduled,
+            }
+            .with_status(row.get(10)?, row.get(11)?))
+        })?;
+
+        let mut racer_heats = Vec::new();
+        for racer_heat in racer_heat_iter {
+            racer_heats.push(racer_heat?);
+        }
+        database::mark_byes(&mut racer_heats);
+
+        Ok(racer_heats)
+    }
+}
+
+impl RaceDataSource for Client {
+    fn kind(&self) -> SourceKind {
+        SourceKind::DerbyNet
+    }
+
+    fn collect_data(&self) -> Result<RaceData, SourceError> {
+        info!(target: "sync", "derbynet::collect_data");
+
+        let data = database::read_snapshot(&self.conn, || {
+            Ok(RaceData {
+                racers: self.select_racers()?,
+                racer_heats: self.select_racer_heats_with_times()?,
+                structure: database::select_structure(&self.conn)?,
+            })
+        })?;
+
+        Ok(data)
+    }
+}
@@ -0,0 +1,37 @@
+use std::sync::Arc;
+
+use crate::lane_stats::TrackHealth;
+use crate::validate::ValidationReport;
+
+/// Progress reported by the sync engine as it runs.
+#[derive(Debug, Clone)]
+pub enum SyncEvent {
+    SyncStarted,
+    SyncStopped,
+    SyncError(String),
+    SyncUpdated(String),
+    UploadQueueUpdated(usize),
+    ValidationReported(ValidationReport),
+    LaneStatsUpdated(TrackHealth),
+}
+
+/// Receives `SyncEvent`s from the engine. The desktop window, the console
+/// and tests each subscribe with their own sink.
+pub trait EventSink: Send + Sync {
+    fn emit(&self, event: SyncEvent);
+}
+
+/// Forwards events over a channel, for callers that would rather await
+/// them than be called back.
+impl EventSink for tokio::sync::mpsc::UnboundedSender<SyncEvent> {
+    fn emit(&self, event: SyncEvent) {
+        // Nobody is listening any more, which is not the engine's problem
+        let _ = self.send(event);
+    }
+}
+
+impl<T: EventSink + ?Sized> EventSink for Arc<T> {
+    fn emit(&self, event: SyncEvent) {
+        (**self).emit(event);
+    }
+}
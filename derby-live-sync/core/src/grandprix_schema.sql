@@ -0,0 +1,35 @@
+-- The parts of a GrandPrix Race Manager database the sync reads, as the
+-- simulator creates them.
+
+CREATE TABLE Classes (ClassID INTEGER PRIMARY KEY, Class TEXT NOT NULL);
+CREATE TABLE Ranks (RankID INTEGER PRIMARY KEY, Rank TEXT NOT NULL, ClassID INTEGER);
+CREATE TABLE RegistrationInfo (
+    RacerID INTEGER PRIMARY KEY,
+    CarNumber INTEGER NOT NULL,
+    LastName TEXT NOT NULL,
+    FirstName TEXT NOT NULL,
+    CarName TEXT,
+    ClassID INTEGER NOT NULL,
+    RankID INTEGER NOT NULL,
+    Exclude INTEGER NOT NULL DEFAULT 0
+);
+CREATE TABLE Rounds (RoundID INTEGER PRIMARY KEY, Round INTEGER NOT NULL, ClassID INTEGER NOT NULL);
+CREATE TABLE RaceChart (
+    ResultID INTEGER PRIMARY KEY,
+    ClassID INTEGER NOT NULL,
+    RoundID INTEGER,
+    Heat INTEGER NOT NULL,
+    Lane INTEGER NOT NULL,
+    RacerID INTEGER NOT NULL,
+    FinishTime REAL,
+    FinishPlace INTEGER,
+    Completed TEXT,
+    IgnoreTime INTEGER NOT NULL DEFAULT 0
+);
+CREATE TABLE RaceInfo (RaceInfoID INTEGER PRIMARY KEY, ItemKey TEXT NOT NULL, ItemValue TEXT);
+
+CREATE VIEW qryRoster AS
+    SELECT ri.RacerID, ri.LastName, ri.FirstName, ri.CarNumber, ri.CarName, c.Class, rk.Rank
+    FROM RegistrationInfo ri
+    INNER JOIN Classes c ON c.ClassID = ri.ClassID
+    INNER JOIN Ranks rk ON rk.RankID = ri.RankID;
@@ -0,0 +1,140 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::database::RacerHeat;
+use crate::source::RaceData;
+use crate::validate;
+
+// Lanes on a healthy track stay within a few hundredths of a second of each
+// other, so a lane that moves this far against the field has changed.
+const DRIFT_THRESHOLD_SECONDS: f64 = 0.05;
+// Fewer heats than this in each half of the event is too noisy to call drift.
+const MIN_DRIFT_HEATS: usize = 3;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaneStats {
+    pub lane_number: i32,
+    pub heats_run: usize,
+    pub average_seconds: Option<f64>,
+    pub variance: Option<f64>,
+    pub wins: usize,
+    pub win_rate: Option<f64>,
+    /// How much slower, against the other lanes in the same heat, the lane
+    /// ran in the second half of its heats than in the first half
+    pub drift_seconds: Option<f64>,
+    pub drifting: bool,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackHealth {
+    pub lanes: Vec<LaneStats>,
+}
+
+#[derive(Debug, Default)]
+struct LaneTally {
+    times: Vec<f64>,
+    placed: usize,
+    wins: usize,
+    // Seconds behind the heat average, in the order the heats were run
+    offsets: Vec<(i64, f64)>,
+}
+
+/// Per-lane statistics over every completed heat. Untimed lanes and the
+/// timer's maximum reading are left out, since they say nothing about the
+/// lane itself.
+pub fn compute(data: &RaceData) -> TrackHealth {
+    let mut heats: HashMap<(Option<i32>, &str, i32), Vec<&RacerHeat>> = HashMap::new();
+    for racer_heat in &data.racer_heats {
+        heats
+            .entry((
+                racer_heat.round_id,
+                racer_heat.group.as_str(),
+                racer_heat.heat_number,
+            ))
+            .or_default()
+            .push(racer_heat);
+    }
+
+    let mut lanes: BTreeMap<i32, LaneTally> = (1..=data.structure.track.lane_count)
+        .filter(|lane| !data.structure.track.unused_lanes.contains(lane))
+        .map(|lane| (lane, LaneTally::default()))
+        .collect();
+
+    for racer_heats in heats.values() {
+        let timed: Vec<(&RacerHeat, f64)> = racer_heats
+            .iter()
+            .filter_map(|rh| rh.finish_seconds.map(|seconds| (*rh, seconds)))
+            .filter(|(_, seconds)| validate::is_real_time(*seconds))
+            .collect();
+        let heat_average = (timed.len() > 1)
+            .then(|| timed.iter().map(|(_, seconds)| seconds).sum::<f64>() / timed.len() as f64);
+        let finished_at = racer_heats
+            .iter()
+            .filter_map(|rh| rh.finished_at_unix)
+            .max();
+
+        for racer_heat in racer_heats {
+            let lane = lanes.entry(racer_heat.lane_number).or_default();
+            if let Some(place) = racer_heat.finish_place {
+                lane.placed += 1;
+                if place == 1 {
+                    lane.wins += 1;
+                }
+            }
+        }
+        for (racer_heat, seconds) in &timed {
+            let lane = lanes.entry(racer_heat.lane_number).or_default();
+            lane.times.push(*seconds);
+            if let (Some(heat_average), Some(finished_at)) = (heat_average, finished_at) {
+                lane.offsets.push((finished_at, seconds - heat_average));
+            }
+        }
+    }
+
+    let lanes = lanes
+        .into_iter()
+        .map(|(lane_number, mut tally)| {
+            tally.offsets.sort_by_key(|(finished_at, _)| *finished_at);
+            let drift_seconds = drift(&tally.offsets);
+            let average_seconds = mean(&tally.times);
+
+            LaneStats {
+                lane_number,
+                heats_run: tally.times.len(),
+                average_seconds,
+                variance: average_seconds.map(|average| {
+                    tally
+                        .times
+                        .iter()
+                        .map(|seconds| (seconds - average).powi(2))
+                        .sum::<f64>()
+                        / tally.times.len() as f64
+                }),
+                wins: tally.wins,
+                win_rate: (tally.placed > 0).then(|| tally.wins as f64 / tally.placed as f64),
+                drift_seconds,
+                drifting: drift_seconds.is_some_and(|drift| drift.abs() >= DRIFT_THRESHOLD_SECONDS),
+            }
+        })
+        .collect();
+
+    TrackHealth { lanes }
+}
+
+fn drift(offsets: &[(i64, f64)]) -> Option<f64> {
+    if offsets.len() < MIN_DRIFT_HEATS * 2 {
+        return None;
+    }
+
+    let (first_half, second_half) = offsets.split_at(offsets.len() / 2);
+    let first: Vec<f64> = first_half.iter().map(|(_, offset)| *offset).collect();
+    let second: Vec<f64> = second_half.iter().map(|(_, offset)| *offset).collect();
+
+    Some(mean(&second)? - mean(&first)?)
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
@@ -0,0 +1,21 @@
+//! The sync engine behind the Derby Live Sync app: reading GrandPrix and
+//! DerbyNet databases, working out what changed and uploading it to
+//! `/api/data`. Nothing here depends on Tauri, so the same engine drives the
+//! desktop app, the headless mode and the integration tests.
+
+pub mod config_dir;
+pub mod database;
+pub mod delta;
+pub mod derbynet;
+pub mod events;
+pub mod lane_stats;
+pub mod mock_server;
+pub mod payload;
+pub mod secrets;
+pub mod settings;
+pub mod simulator;
+pub mod source;
+pub mod standings;
+pub mod synchronize;
+pub mod upload_queue;
+pub mod validate;
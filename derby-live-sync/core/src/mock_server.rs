@@ -0,0 +1,336 @@
+//! A stand-in for the Derby Live server, for rehearsing race day without the
+//! real deployment. It takes `POST /api/data` exactly as `DataController.import`
+//! does, keeps every upload it receives, and serves the heats page the way
+//! the live site would show it.
+
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use log::info;
+use rand::{distributions::Alphanumeric, Rng};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Arc, Mutex},
+};
+use tokio::net::TcpListener;
+
+use crate::database::{Racer, RacerHeat};
+use crate::payload::{RequestData, ResponseData, INVALID_EVENT_KEY};
+use crate::standings::Standing;
+
+/// How often the heats page reloads itself. The live site pushes updates
+/// instead, but polling is close enough for a rehearsal.
+const REFRESH_SECONDS: u32 = 2;
+
+/// What the server holds for the event, merged the way the importer upserts
+/// rows: racers by `racer_id` and heat results by `result_id`. Removed rows
+/// are left in place because Derby Live does not delete them either.
+#[derive(Debug, Default)]
+struct MockEvent {
+    racers: BTreeMap<i32, Racer>,
+    racer_heats: BTreeMap<i32, RacerHeat>,
+    standings: Vec<Standing>,
+    uploads: Vec<RequestData<'static>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MockServer {
+    api_key: String,
+    event_key: String,
+    event: Arc<Mutex<MockEvent>>,
+}
+
+/// The heats page for the event, worked out the same way `HeatLive.Index`
+/// does it: heats are grouped by number, and the current heat is the first
+/// one with a car still missing a place.
+#[derive(Debug, Clone, Serialize)]
+pub struct LiveView {
+    pub current_heat: Option<Heat>,
+    pub next_heat: Option<Heat>,
+    pub heats: Vec<Heat>,
+    pub racers: Vec<Racer>,
+    pub standings: Vec<Standing>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Heat {
+    pub heat_number: i32,
+    pub lanes: Vec<Lane>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Lane {
+    pub lane_number: i32,
+    pub car_number: i32,
+    pub racer_name: Option<String>,
+    pub finish_seconds: Option<f64>,
+    pub finish_place: Option<i32>,
+}
+
+impl Heat {
+    fn is_finished(&self) -> bool {
+        self.lanes.iter().all(|lane| lane.finish_place.is_some())
+    }
+}
+
+/// A random key of `len` characters, in a shape the settings accept, for
+/// rehearsing without real Derby Live keys.
+pub fn generate_key(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+impl MockServer {
+    pub fn new(api_key: String, event_key: String) -> MockServer {
+        MockServer {
+            api_key,
+            event_key,
+            event: Arc::new(Mutex::new(MockEvent::default())),
+        }
+    }
+
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/api/data", post(import))
+            .route("/api/uploads", get(uploads))
+            .route("/{event_key}/heats", get(heats_page))
+            .route("/{event_key}/heats.json", get(heats_json))
+            .with_state(self.clone())
+    }
+
+    /// Serves until the listener fails. Bind to port 0 to let the OS pick a
+    /// free port, then read it back from the listener.
+    pub async fn serve(self, listener: TcpListener) -> std::io::Result<()> {
+        info!(target: "mock_server", "serve: listening on {:?}", listener.local_addr());
+        axum::serve(listener, self.router()).await
+    }
+
+    /// Every accepted upload, oldest first.
+    pub fn uploads(&self) -> Vec<RequestData<'static>> {
+        self.event
+            .lock()
+            .expect("mock event poisoned")
+            .uploads
+            .clone()
+    }
+
+    pub fn live_view(&self) -> LiveView {
+        let event = self.event.lock().expect("mock event poisoned");
+
+        let mut by_number: BTreeMap<i32, Vec<Lane>> = BTreeMap::new();
+        for racer_heat in event.racer_heats.values() {
+            let racer = event.racers.get(&racer_heat.racer_id);
+            by_number
+                .entry(racer_heat.heat_number)
+                .or_default()
+                .push(Lane {
+                    lane_number: racer_heat.lane_number,
+                    car_number: racer.map_or(racer_heat.car_number, |r| r.car_number),
+                    racer_name: racer.map(|r| format!("{} {}", r.first_name, r.last_name)),
+                    finish_seconds: racer_heat.finish_seconds,
+                    finish_place: racer_heat.finish_place,
+                });
+        }
+        let heats: Vec<Heat> = by_number
+            .into_iter()
+            .map(|(heat_number, mut lanes)| {
+                lanes.sort_by_key(|lane| lane.lane_number);
+                Heat { heat_number, lanes }
+            })
+            .collect();
+
+        let mut unfinished = heats.iter().filter(|heat| !heat.is_finished());
+        let first_unfinished = unfinished.next().cloned();
+        let next_heat = unfinished.next().cloned();
+        let current_heat =
+            first_unfinished.or_else(|| heats.iter().rev().find(|h| h.is_finished()).cloned());
+
+        let mut racers: Vec<Racer> = event.racers.values().cloned().collect();
+        racers.sort_by_key(|racer| racer.car_number);
+
+        LiveView {
+            current_heat,
+            next_heat,
+            heats,
+            racers,
+            standings: event.standings.clone(),
+        }
+    }
+
+    fn accept(&self, request: RequestData<'static>) {
+        let mut event = self.event.lock().expect("mock event poisoned");
+
+        for racer in request.racers.iter() {
+            event.racers.insert(racer.racer_id, racer.clone());
+        }
+        for racer_heat in request.racer_heats.iter() {
+            event
+                .racer_heats
+                .insert(racer_heat.result_id, racer_heat.clone());
+        }
+        if let Some(standings) = &request.standings {
+            event.standings = standings.to_vec();
+        }
+
+        info!(
+            target: "mock_server",
+            "accept: {} racers, {} racer_heats",
+            request.racers.len(),
+            request.racer_heats.len()
+        );
+        event.uploads.push(request);
+    }
+}
+
+async fn import(State(server): State<MockServer>, headers: HeaderMap, body: Bytes) -> Response {
+    let api_key = headers
+        .get("x-api-key")
+        .and_then(|value| value.to_str().ok());
+    if api_key != Some(server.api_key.as_str()) {
+        let body = ResponseData {
+            error: Some("Invalid API key".to_string()),
+            ..Default::default()
+        };
+        return (StatusCode::UNAUTHORIZED, Json(body)).into_response();
+    }
+
+    // Parsed only after the key check, as the API pipeline does it
+    let request: RequestData<'static> = match serde_json::from_slice(&body) {
+        Ok(request) => request,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+
+    if request.event_key != server.event_key {
+        let body = ResponseData {
+            status: Some("error".to_string()),
+            message: Some(INVALID_EVENT_KEY.to_string()),
+            ..Default::default()
+        };
+        return Json(body).into_response();
+    }
+
+    server.accept(request);
+    Json(ResponseData {
+        status: Some("ok".to_string()),
+        ..Default::default()
+    })
+    .into_response()
+}
+
+async fn uploads(State(server): State<MockServer>) -> Json<Vec<RequestData<'static>>> {
+    Json(server.uploads())
+}
+
+async fn heats_json(
+    State(server): State<MockServer>,
+    Path(event_key): Path<String>,
+) -> Result<Json<LiveView>, StatusCode> {
+    if event_key != server.event_key {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(server.live_view()))
+}
+
+async fn heats_page(
+    State(server): State<MockServer>,
+    Path(event_key): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    if event_key != server.event_key {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Html(render(&event_key, &server.live_view())))
+}
+
+fn render(event_key: &str, view: &LiveView) -> String {
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\">\
+         <meta http-equiv=\"refresh\" content=\"{}\">\
+         <title>{} heats (mock)</title></head><body>",
+        REFRESH_SECONDS,
+        escape(event_key)
+    );
+    let _ = write!(html, "<h1>{} heats</h1>", escape(event_key));
+
+    if view.heats.is_empty() {
+        html.push_str("<p>No heats uploaded yet.</p>");
+    }
+    if let Some(heat) = &view.current_heat {
+        render_heat(&mut html, "Current heat", heat);
+    }
+    if let Some(heat) = &view.next_heat {
+        render_heat(&mut html, "Next heat", heat);
+    }
+
+    if !view.standings.is_empty() {
+        html.push_str("<h2>Standings</h2><table><tr><th>Group</th><th>Rank</th><th>Car</th><th>Heats</th><th>Average</th><th>Points</th></tr>");
+        for standing in &view.standings {
+            let _ = write!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(&standing.group),
+                standing.rank_in_group,
+                standing.car_number,
+                standing.heats_run,
+                seconds(standing.average_seconds),
+                standing.points
+            );
+        }
+        html.push_str("</table>");
+    }
+
+    html.push_str("<h2>Racers</h2><ul>");
+    for racer in &view.racers {
+        let _ = write!(
+            html,
+            "<li>{} - {} {}</li>",
+            racer.car_number,
+            escape(&racer.first_name),
+            escape(&racer.last_name)
+        );
+    }
+    html.push_str("</ul></body></html>");
+    html
+}
+
+fn render_heat(html: &mut String, title: &str, heat: &Heat) {
+    let _ = write!(
+        html,
+        "<h2>{}: heat {}</h2><table><tr><th>Lane</th><th>Racer</th><th>Time</th><th>Place</th></tr>",
+        title, heat.heat_number
+    );
+    for lane in &heat.lanes {
+        let _ = write!(
+            html,
+            "<tr><td>{}</td><td>{} - {}</td><td>{}</td><td>{}</td></tr>",
+            lane.lane_number,
+            lane.car_number,
+            escape(lane.racer_name.as_deref().unwrap_or("")),
+            seconds(lane.finish_seconds),
+            lane.finish_place.map(|p| p.to_string()).unwrap_or_default()
+        );
+    }
+    html.push_str("</table>");
+}
+
+fn seconds(value: Option<f64>) -> String {
+    value.map(|s| format!("{:.3}", s)).unwrap_or_default()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
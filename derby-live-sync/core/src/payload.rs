@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+use crate::database::{Racer, RacerHeat};
+use crate::source::RaceStructure;
+use crate::standings::Standing;
+use crate::upload_queue::QueuedUpload;
+
+/// Body of `POST /api/data`. Borrows from a queued upload when sending, and
+/// owns its rows when read back by a server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestData<'a> {
+    pub event_key: String,
+    #[serde(default)]
+    pub racers: Cow<'a, [Racer]>,
+    #[serde(default)]
+    pub racer_heats: Cow<'a, [RacerHeat]>,
+    #[serde(default, skip_serializing_if = "<[i32]>::is_empty")]
+    pub removed_racer_ids: Cow<'a, [i32]>,
+    #[serde(default, skip_serializing_if = "<[i32]>::is_empty")]
+    pub removed_result_ids: Cow<'a, [i32]>,
+    #[serde(flatten)]
+    pub structure: Option<Cow<'a, RaceStructure>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub standings: Option<Cow<'a, [Standing]>>,
+}
+
+impl<'a> From<&'a QueuedUpload> for RequestData<'a> {
+    fn from(upload: &'a QueuedUpload) -> Self {
+        let delta = &upload.delta;
+        RequestData {
+            event_key: upload.event_key.clone(),
+            racers: Cow::Borrowed(&delta.racers),
+            racer_heats: Cow::Borrowed(&delta.racer_heats),
+            removed_racer_ids: Cow::Borrowed(&delta.removed_racer_ids),
+            removed_result_ids: Cow::Borrowed(&delta.removed_result_ids),
+            structure: delta.structure.as_ref().map(Cow::Borrowed),
+            standings: delta.standings.as_deref().map(Cow::Borrowed),
+        }
+    }
+}
+
+/// The message `DataController.import` returns for an unknown event key
+pub const INVALID_EVENT_KEY: &str = "Invalid event key";
+
+/// Body returned by `DataController.import`, or by `ApiAuth` when the API
+/// key is rejected.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ResponseData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
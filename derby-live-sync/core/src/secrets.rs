@@ -0,0 +1,226 @@
+//! Keeps the Derby Live API key out of `settings.json`. The key goes to the
+//! platform secret store (Keychain, Credential Manager or Secret Service),
+//! and only when that is unavailable, as on a Linux box with no keyring
+//! daemon, to a file encrypted with a key kept beside it. That fallback is
+//! obfuscation, not protection: anyone who can read the one file can read
+//! the other.
+
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use log::{info, warn};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::config_dir;
+
+const SERVICE: &str = "derby-live-sync";
+const ACCOUNT: &str = "api_key";
+const FALLBACK_FILE: &str = "api_key.enc";
+const FALLBACK_KEY_FILE: &str = "api_key.key";
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug)]
+pub enum SecretError {
+    Io(std::io::Error),
+    /// The fallback file could not be decrypted with the key beside it
+    Corrupt,
+}
+
+impl fmt::Display for SecretError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretError::Io(e) => write!(f, "failed to save API key: {}", e),
+            SecretError::Corrupt => write!(f, "the saved API key could not be read"),
+        }
+    }
+}
+
+impl From<std::io::Error> for SecretError {
+    fn from(e: std::io::Error) -> Self {
+        SecretError::Io(e)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SecretStore {
+    /// Where the encrypted fallback lives
+    dir: PathBuf,
+    /// The keyring entry's account name
+    account: String,
+    use_keyring: bool,
+}
+
+impl SecretStore {
+    pub fn new(dir: PathBuf) -> SecretStore {
+        SecretStore {
+            dir,
+            account: ACCOUNT.to_string(),
+            use_keyring: true,
+        }
+    }
+
+    /// Skips the platform secret store, so tests never touch the real one.
+    pub fn file_only(dir: PathBuf) -> SecretStore {
+        SecretStore {
+            dir,
+            account: ACCOUNT.to_string(),
+            use_keyring: false,
+        }
+    }
+
+    pub fn in_config_dir() -> SecretStore {
+        SecretStore::new(config_dir::path())
+    }
+
+    /// The store for one profile's key, with a keyring entry and fallback
+    /// files of its own.
+    pub fn for_profile(&self, profile: &str) -> SecretStore {
+        SecretStore {
+            dir: config_dir::profile_dir_in(&self.dir, profile),
+            account: format!("{}:{}", ACCOUNT, profile),
+            use_keyring: self.use_keyring,
+        }
+    }
+
+    pub fn load_api_key(&self) -> Option<String> {
+        if self.use_keyring {
+            match on_keyring_thread(&self.account, |entry| entry.get_password()) {
+                Ok(api_key) => return Some(api_key),
+                Err(keyring::Error::NoEntry) => {}
+                Err(e) => warn!(target: "secrets", "load_api_key: keyring unavailable: {}", e),
+            }
+        }
+
+        match read_fallback(&self.dir) {
+            Ok(api_key) => api_key,
+            Err(e) => {
+                warn!(target: "secrets", "load_api_key: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Saves to the secret store if it can, and to the encrypted file if it
+    /// cannot. Whichever copy is not current is removed.
+    pub fn store_api_key(&self, api_key: &str) -> Result<(), SecretError> {
+        if self.use_keyring {
+            let password = api_key.to_string();
+            match on_keyring_thread(&self.account, move |entry| entry.set_password(&password)) {
+                Ok(()) => {
+                    info!(target: "secrets", "store_api_key: saved to keyring");
+                    return remove_fallback(&self.dir);
+                }
+                Err(e) => warn!(target: "secrets", "store_api_key: keyring unavailable: {}", e),
+            }
+        }
+
+        write_fallback(&self.dir, api_key)?;
+        info!(target: "secrets", "store_api_key: saved to encrypted file");
+        Ok(())
+    }
+
+    pub fn clear_api_key(&self) -> Result<(), SecretError> {
+        if self.use_keyring {
+            match on_keyring_thread(&self.account, |entry| entry.delete_credential()) {
+                Ok(()) | Err(keyring::Error::NoEntry) => {}
+                Err(e) => warn!(target: "secrets", "clear_api_key: keyring unavailable: {}", e),
+            }
+        }
+        remove_fallback(&self.dir)
+    }
+}
+
+/// The async Secret Service backend deadlocks when called from a thread
+/// driving a Tokio runtime, so every keyring call gets a thread of its own.
+fn on_keyring_thread<T: Send + 'static>(
+    account: &str,
+    f: impl FnOnce(&keyring::Entry) -> keyring::Result<T> + Send + 'static,
+) -> keyring::Result<T> {
+    let account = account.to_string();
+    std::thread::spawn(move || keyring::Entry::new(SERVICE, &account).and_then(|entry| f(&entry)))
+        .join()
+        .unwrap_or_else(|_| {
+            Err(keyring::Error::PlatformFailure(
+                "keyring thread panicked".into(),
+            ))
+        })
+}
+
+fn read_fallback(dir: &Path) -> Result<Option<String>, SecretError> {
+    let contents = match std::fs::read(dir.join(FALLBACK_FILE)) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let key = std::fs::read(dir.join(FALLBACK_KEY_FILE))?;
+    if key.len() != 32 || contents.len() < NONCE_LEN {
+        return Err(SecretError::Corrupt);
+    }
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+    let (nonce, ciphertext) = contents.split_at(NONCE_LEN);
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| SecretError::Corrupt)?;
+
+    String::from_utf8(plaintext)
+        .map(Some)
+        .map_err(|_| SecretError::Corrupt)
+}
+
+/// Keeps the key out of plain sight and out of anything that greps for it,
+/// but the encryption key sits in the same directory, so only the file mode
+/// on Unix, and nothing at all on Windows, stops another account reading it.
+fn write_fallback(dir: &Path, api_key: &str) -> Result<(), SecretError> {
+    std::fs::create_dir_all(dir)?;
+    let key_path = dir.join(FALLBACK_KEY_FILE);
+    let key = match std::fs::read(&key_path) {
+        Ok(key) if key.len() == 32 => *Key::from_slice(&key),
+        _ => {
+            let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+            write_private(&key_path, &key)?;
+            key
+        }
+    };
+
+    let cipher = ChaCha20Poly1305::new(&key);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let mut contents = nonce.to_vec();
+    contents.extend(
+        cipher
+            .encrypt(&nonce, api_key.as_bytes())
+            .map_err(|_| SecretError::Corrupt)?,
+    );
+
+    write_private(&dir.join(FALLBACK_FILE), &contents)?;
+    Ok(())
+}
+
+fn remove_fallback(dir: &Path) -> Result<(), SecretError> {
+    for name in [FALLBACK_FILE, FALLBACK_KEY_FILE] {
+        match std::fs::remove_file(dir.join(name)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Writes a file only the current user can read, where the platform allows.
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    use std::io::Write;
+    options.open(path)?.write_all(contents)
+}
@@ -0,0 +1,690 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config_dir;
+use crate::secrets::SecretStore;
+use crate::source::SourceKind;
+use crate::standings::ScoringMethod;
+use crate::synchronize::SyncOptions;
+use crate::upload_queue::QUEUE_FILE;
+
+const SETTINGS_FILE: &str = "settings.json";
+
+/// Bumped whenever settings.json changes shape, with a step in `migrate` to
+/// bring older files up to date.
+///
+/// 1. Unversioned, with the API key in the file
+/// 2. The API key moved to the secret store
+/// 3. Named server targets
+/// 4. The event, database, server and API key moved into named profiles
+pub const SETTINGS_VERSION: u32 = 4;
+
+/// The profile settings from before profiles are moved into
+pub const DEFAULT_PROFILE: &str = "Default";
+
+/// Lengths of the keys Derby Live generates: 24 and 48 random bytes, URL
+/// safe Base64 encoded.
+pub const EVENT_KEY_LEN: usize = 32;
+pub const API_KEY_LEN: usize = 64;
+
+// Long enough to span a couple of poll intervals, since one heat save can
+// touch the database file several times.
+const DEFAULT_SYNC_DEBOUNCE_MS: u64 = 1500;
+const MAX_SYNC_DEBOUNCE_MS: u64 = 60_000;
+
+const PRODUCTION_URL: &str = "https://derby-live.fly.dev";
+const LOCAL_DEVELOPMENT_URL: &str = "http://localhost:4000";
+/// Where `--mock-server` listens when not given a port
+pub const LOCAL_MOCK_PORT: u16 = 4001;
+
+/// The server used until one is saved in settings.
+fn get_server_url() -> String {
+    #[cfg(feature = "production")]
+    {
+        PRODUCTION_URL.to_string()
+    }
+    #[cfg(not(feature = "production"))]
+    {
+        LOCAL_DEVELOPMENT_URL.to_string()
+    }
+}
+
+/// A Derby Live server to pick from in settings. Staging and self-hosted
+/// instances are added alongside the built-in ones.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerTarget {
+    pub name: String,
+    pub url: String,
+}
+
+fn default_server_targets() -> Vec<ServerTarget> {
+    let local_mock_url = format!("http://localhost:{}", LOCAL_MOCK_PORT);
+    [
+        ("Production", PRODUCTION_URL),
+        ("Local development", LOCAL_DEVELOPMENT_URL),
+        ("Local mock", local_mock_url.as_str()),
+    ]
+    .into_iter()
+    .map(|(name, url)| ServerTarget {
+        name: name.to_string(),
+        url: url.to_string(),
+    })
+    .collect()
+}
+
+/// Why settings could not be loaded or saved, in a shape the settings window
+/// can show next to the fields at fault.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum SettingsError {
+    NotFound,
+    Unreadable {
+        message: String,
+    },
+    /// Not JSON, or a field of the wrong type
+    Malformed {
+        message: String,
+    },
+    /// Written by a newer version of the app
+    UnsupportedVersion {
+        version: u32,
+    },
+    MigrationFailed {
+        from: u32,
+        message: String,
+    },
+    Invalid {
+        fields: Vec<FieldError>,
+    },
+    UnknownProfile {
+        name: String,
+    },
+    Unwritable {
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldError {
+    /// The field's name as the settings window knows it
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsError::NotFound => write!(f, "no settings.json found"),
+            SettingsError::Unreadable { message } => {
+                write!(f, "settings.json could not be read: {}", message)
+            }
+            SettingsError::Malformed { message } => {
+                write!(f, "settings.json is damaged: {}", message)
+            }
+            SettingsError::UnsupportedVersion { version } => write!(
+                f,
+                "settings.json is version {}, but this app only knows up to {}",
+                version, SETTINGS_VERSION
+            ),
+            SettingsError::MigrationFailed { from, message } => write!(
+                f,
+                "settings.json could not be upgraded from version {}: {}",
+                from, message
+            ),
+            SettingsError::Invalid { fields } => {
+                let messages: Vec<String> = fields
+                    .iter()
+                    .map(|field| format!("{}: {}", field.field, field.message))
+                    .collect();
+                write!(f, "invalid settings: {}", messages.join("; "))
+            }
+            SettingsError::UnknownProfile { name } => {
+                write!(f, "there is no profile called {}", name)
+            }
+            SettingsError::Unwritable { message } => {
+                write!(f, "settings could not be saved: {}", message)
+            }
+        }
+    }
+}
+
+/// One event's worth of settings. Packs racing on the same day each get a
+/// profile, and any number of them can sync at once.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    pub name: String,
+    /// Kept in the secret store rather than settings.json
+    #[serde(skip)]
+    pub api_key: Option<String>,
+    pub event_key: Option<String>,
+    pub database_path: Option<PathBuf>,
+    #[serde(default = "get_server_url")]
+    pub server_url: String,
+}
+
+impl fmt::Debug for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Profile")
+            .field("name", &self.name)
+            .field("api_key", &self.api_key.as_ref().map(|_| "<redacted>"))
+            .field("event_key", &self.event_key)
+            .field("database_path", &self.database_path)
+            .field("server_url", &self.server_url)
+            .finish()
+    }
+}
+
+impl Profile {
+    pub fn new(name: String) -> Profile {
+        Profile {
+            name,
+            api_key: Default::default(),
+            event_key: Default::default(),
+            database_path: Default::default(),
+            server_url: get_server_url(),
+        }
+    }
+
+    /// Saves the key to this profile's place in the secret store and uses
+    /// it from now on.
+    pub fn set_api_key(&mut self, api_key: String) -> Result<(), SettingsError> {
+        SecretStore::in_config_dir()
+            .for_profile(&self.name)
+            .store_api_key(&api_key)
+            .map_err(|e| SettingsError::Unwritable {
+                message: e.to_string(),
+            })?;
+        self.api_key = Some(api_key);
+        Ok(())
+    }
+
+    /// Checks the profile's own fields the way `AppSettings::validate` does.
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        let mut fields = Vec::new();
+        self.check(&mut fields);
+        if fields.is_empty() {
+            Ok(())
+        } else {
+            Err(SettingsError::Invalid { fields })
+        }
+    }
+
+    fn check(&self, fields: &mut Vec<FieldError>) {
+        let mut invalid = |field: &str, message: String| {
+            fields.push(FieldError {
+                field: field.to_string(),
+                message,
+            })
+        };
+
+        if let Err(message) = check_server_url(&self.server_url) {
+            invalid("serverUrl", message);
+        }
+        if let Some(Err(message)) = self
+            .api_key
+            .as_deref()
+            .map(|key| check_key(key, API_KEY_LEN))
+        {
+            invalid("apiKey", message);
+        }
+        if let Some(Err(message)) = self
+            .event_key
+            .as_deref()
+            .map(|key| check_key(key, EVENT_KEY_LEN))
+        {
+            invalid("eventKey", message);
+        }
+        if let Some(path) = &self.database_path {
+            if !path.is_file() {
+                invalid("databasePath", format!("{} does not exist", path.display()));
+            }
+        }
+    }
+
+    pub fn current_database_path(&self) -> String {
+        self.database_path
+            .as_ref()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    pub fn update_database_path_if_exists<P: AsRef<Path>>(&mut self, path: P) {
+        if path.as_ref().exists() {
+            self.database_path = Some(path.as_ref().to_path_buf());
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AppSettings {
+    pub version: u32,
+    /// The profile the windows show and edit
+    #[serde(default)]
+    pub active_profile: String,
+    #[serde(default)]
+    pub profiles: Vec<Profile>,
+    #[serde(default = "default_server_targets")]
+    pub server_targets: Vec<ServerTarget>,
+    #[serde(default)]
+    pub source_kind: SourceKind,
+    #[serde(default = "default_sync_debounce_ms")]
+    pub sync_debounce_ms: u64,
+    #[serde(default)]
+    pub scoring_method: ScoringMethod,
+    #[serde(default)]
+    pub warnings_block_sync: bool,
+}
+
+fn default_sync_debounce_ms() -> u64 {
+    DEFAULT_SYNC_DEBOUNCE_MS
+}
+
+impl fmt::Debug for AppSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AppSettings")
+            .field("version", &self.version)
+            .field("active_profile", &self.active_profile)
+            .field("profiles", &self.profiles)
+            .field("server_targets", &self.server_targets)
+            .field("source_kind", &self.source_kind)
+            .field("sync_debounce_ms", &self.sync_debounce_ms)
+            .field("scoring_method", &self.scoring_method)
+            .field("warnings_block_sync", &self.warnings_block_sync)
+            .finish()
+    }
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            active_profile: DEFAULT_PROFILE.to_string(),
+            profiles: vec![Profile::new(DEFAULT_PROFILE.to_string())],
+            server_targets: default_server_targets(),
+            source_kind: Default::default(),
+            sync_debounce_ms: DEFAULT_SYNC_DEBOUNCE_MS,
+            scoring_method: Default::default(),
+            warnings_block_sync: false,
+        }
+    }
+}
+
+impl AppSettings {
+    pub fn load() -> Result<Self, SettingsError> {
+        AppSettings::load_from(&config_dir::path(), &SecretStore::in_config_dir())
+    }
+
+    /// Reads settings.json from `dir`, bringing it up to the current version
+    /// and saving it back if it was older. A file that cannot be read is
+    /// copied aside as settings.json.bak, so saving over it loses nothing.
+    pub fn load_from(dir: &Path, secrets: &SecretStore) -> Result<Self, SettingsError> {
+        let path = dir.join(SETTINGS_FILE);
+        info!(target: "settings::AppSettings::init", "path: {:?}", path);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                info!(target: "setup", "no settings.json found");
+                return Err(SettingsError::NotFound);
+            }
+            Err(e) => {
+                return Err(SettingsError::Unreadable {
+                    message: e.to_string(),
+                })
+            }
+        };
+
+        let result = AppSettings::parse(&contents, dir, secrets);
+        let mut app_settings = match result {
+            Ok((app_settings, migrated)) => {
+                if migrated {
+                    app_settings
+                        .write_to(dir)
+                        .map_err(|e| SettingsError::Unwritable {
+                            message: e.to_string(),
+                        })?;
+                }
+                app_settings
+            }
+            Err(e) => {
+                if let Err(copy_error) = std::fs::copy(&path, dir.join("settings.json.bak")) {
+                    warn!(target: "settings::AppSettings::init", "failed to back up settings.json: {}", copy_error);
+                }
+                return Err(e);
+            }
+        };
+
+        if app_settings.profiles.is_empty() {
+            app_settings
+                .profiles
+                .push(Profile::new(DEFAULT_PROFILE.to_string()));
+        }
+        if app_settings.profile(&app_settings.active_profile).is_none() {
+            app_settings.active_profile = app_settings.profiles[0].name.clone();
+        }
+        for profile in app_settings.profiles.iter_mut() {
+            profile.api_key = secrets.for_profile(&profile.name).load_api_key();
+        }
+        if let Err(e) = app_settings.validate() {
+            // Still usable; the settings window shows what to fix
+            warn!(target: "settings::AppSettings::init", "{}", e);
+        }
+        info!(target: "settings::AppSettings::init", "app_settings: {:?}", app_settings);
+
+        Ok(app_settings)
+    }
+
+    /// Returns the settings and whether they had to be migrated.
+    fn parse(
+        contents: &str,
+        dir: &Path,
+        secrets: &SecretStore,
+    ) -> Result<(Self, bool), SettingsError> {
+        let malformed = |e: serde_json::Error| SettingsError::Malformed {
+            message: e.to_string(),
+        };
+
+        let mut settings: Map<String, Value> = serde_json::from_str(contents).map_err(malformed)?;
+        let migrated = migrate(&mut settings, dir, secrets)?;
+        let app_settings = serde_json::from_value(Value::Object(settings)).map_err(malformed)?;
+
+        Ok((app_settings, migrated))
+    }
+
+    pub fn write(&self) -> std::io::Result<()> {
+        self.write_to(&config_dir::path())
+    }
+
+    pub fn write_to(&self, dir: &Path) -> std::io::Result<()> {
+        info!(target: "settings::AppSettings::write", "settings: {:?}", self);
+
+        let file_contents = serde_json::to_string_pretty(self)?;
+        config_dir::write_atomic(&dir.join(SETTINGS_FILE), file_contents.as_bytes())
+    }
+
+    pub fn profile(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+
+    pub fn profile_mut(&mut self, name: &str) -> Option<&mut Profile> {
+        self.profiles
+            .iter_mut()
+            .find(|profile| profile.name == name)
+    }
+
+    /// The profile being shown. Loading makes sure there is one.
+    pub fn active(&self) -> &Profile {
+        self.profile(&self.active_profile)
+            .expect("active profile is missing")
+    }
+
+    pub fn active_mut(&mut self) -> &mut Profile {
+        let name = self.active_profile.clone();
+        self.profile_mut(&name).expect("active profile is missing")
+    }
+
+    pub fn switch_profile(&mut self, name: &str) -> Result<(), SettingsError> {
+        if self.profile(name).is_none() {
+            return Err(SettingsError::UnknownProfile {
+                name: name.to_string(),
+            });
+        }
+        self.active_profile = name.to_string();
+        Ok(())
+    }
+
+    /// Adds an empty profile on the active one's server and switches to it.
+    pub fn add_profile(&mut self, name: &str) -> Result<(), SettingsError> {
+        let name = name.trim();
+        let mut profile = Profile::new(name.to_string());
+        profile.server_url = self.active().server_url.clone();
+
+        let mut candidate = self.clone();
+        candidate.profiles.push(profile);
+        let mut fields = Vec::new();
+        check_profile_names(&candidate.profiles, &mut fields);
+        if !fields.is_empty() {
+            return Err(SettingsError::Invalid { fields });
+        }
+
+        *self = candidate;
+        self.active_profile = name.to_string();
+        Ok(())
+    }
+
+    /// Checks every field of the active profile and the settings shared by
+    /// all of them, reporting all that are wrong rather than the first.
+    /// Unset keys and paths are left to the synchronizer to complain about,
+    /// since settings are saved a piece at a time.
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        let mut fields = Vec::new();
+        if let Some(profile) = self.profile(&self.active_profile) {
+            profile.check(&mut fields);
+        }
+        if let Err(message) = check_server_targets(&self.server_targets) {
+            fields.push(FieldError {
+                field: "serverTargets".to_string(),
+                message,
+            });
+        }
+        check_profile_names(&self.profiles, &mut fields);
+        if !(1..=MAX_SYNC_DEBOUNCE_MS).contains(&self.sync_debounce_ms) {
+            fields.push(FieldError {
+                field: "syncDebounceMs".to_string(),
+                message: format!("must be between 1 and {}", MAX_SYNC_DEBOUNCE_MS),
+            });
+        }
+
+        if fields.is_empty() {
+            Ok(())
+        } else {
+            Err(SettingsError::Invalid { fields })
+        }
+    }
+
+    pub fn sync_options(&self) -> SyncOptions {
+        SyncOptions {
+            source_kind: self.source_kind,
+            debounce: Duration::from_millis(self.sync_debounce_ms),
+            scoring_method: self.scoring_method,
+            warnings_block_sync: self.warnings_block_sync,
+        }
+    }
+
+    /// The URL of the target called `name_or_url`, ignoring case, or the
+    /// argument itself when no target has that name.
+    pub fn resolve_server(&self, name_or_url: &str) -> String {
+        self.server_targets
+            .iter()
+            .find(|target| target.name.eq_ignore_ascii_case(name_or_url))
+            .map(|target| target.url.clone())
+            .unwrap_or_else(|| normalize_server_url(name_or_url))
+    }
+
+    /// The name of the target the active profile's server belongs to, if
+    /// any.
+    pub fn server_target_name(&self) -> Option<&str> {
+        let server_url = &self.active().server_url;
+        self.server_targets
+            .iter()
+            .find(|target| &target.url == server_url)
+            .map(|target| target.name.as_str())
+    }
+}
+
+/// Brings a settings file up to `SETTINGS_VERSION` one version at a time,
+/// returning whether anything changed.
+fn migrate(
+    settings: &mut Map<String, Value>,
+    dir: &Path,
+    secrets: &SecretStore,
+) -> Result<bool, SettingsError> {
+    let version = match settings.get("version") {
+        None => 1,
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| SettingsError::Malformed {
+                message: format!("version {} is not a number", version),
+            })?,
+    };
+    if version > SETTINGS_VERSION {
+        return Err(SettingsError::UnsupportedVersion { version });
+    }
+
+    for from in version..SETTINGS_VERSION {
+        info!(target: "settings::migrate", "migrating from version {}", from);
+        let step = match from {
+            1 => move_api_key_to_secret_store(settings, secrets),
+            2 => add_server_targets(settings),
+            3 => move_into_default_profile(settings, dir, secrets),
+            _ => Ok(()),
+        };
+        step.map_err(|message| SettingsError::MigrationFailed { from, message })?;
+    }
+
+    settings.insert("version".to_string(), Value::from(SETTINGS_VERSION));
+    Ok(version < SETTINGS_VERSION)
+}
+
+fn move_api_key_to_secret_store(
+    settings: &mut Map<String, Value>,
+    secrets: &SecretStore,
+) -> Result<(), String> {
+    match settings.remove("apiKey") {
+        Some(Value::String(api_key)) if !api_key.is_empty() => {
+            secrets.store_api_key(&api_key).map_err(|e| e.to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Up to version 2 the server URL was replaced by the build's default on
+/// every load, so there was only ever one target.
+fn add_server_targets(settings: &mut Map<String, Value>) -> Result<(), String> {
+    let targets = serde_json::to_value(default_server_targets()).map_err(|e| e.to_string())?;
+    settings.entry("serverTargets").or_insert(targets);
+    Ok(())
+}
+
+/// Everything from before profiles belongs to one event, so it becomes the
+/// one profile, API key and pending uploads included.
+fn move_into_default_profile(
+    settings: &mut Map<String, Value>,
+    dir: &Path,
+    secrets: &SecretStore,
+) -> Result<(), String> {
+    let mut profile = Map::new();
+    profile.insert("name".to_string(), Value::from(DEFAULT_PROFILE));
+    for field in ["eventKey", "databasePath", "serverUrl"] {
+        if let Some(value) = settings.remove(field) {
+            profile.insert(field.to_string(), value);
+        }
+    }
+    settings.insert(
+        "profiles".to_string(),
+        Value::Array(vec![Value::Object(profile)]),
+    );
+    settings.insert("activeProfile".to_string(), Value::from(DEFAULT_PROFILE));
+
+    if let Some(api_key) = secrets.load_api_key() {
+        secrets
+            .for_profile(DEFAULT_PROFILE)
+            .store_api_key(&api_key)
+            .and_then(|_| secrets.clear_api_key())
+            .map_err(|e| e.to_string())?;
+    }
+
+    let queue = dir.join(QUEUE_FILE);
+    let profile_queue = config_dir::profile_dir_in(dir, DEFAULT_PROFILE).join(QUEUE_FILE);
+    if queue.is_file() && !profile_queue.exists() {
+        std::fs::create_dir_all(profile_queue.parent().unwrap_or(dir))
+            .and_then(|_| std::fs::rename(&queue, &profile_queue))
+            .map_err(|e| format!("failed to move {}: {}", queue.display(), e))?;
+    }
+    Ok(())
+}
+
+fn check_server_url(server_url: &str) -> Result<(), String> {
+    let url = reqwest::Url::parse(server_url).map_err(|e| format!("not a URL: {}", e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("must start with http:// or https://".to_string());
+    }
+    if url.host_str().is_none() {
+        return Err("has no host name".to_string());
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err("should be the site address, with no query or fragment".to_string());
+    }
+    Ok(())
+}
+
+/// Drops surrounding whitespace and any trailing slash, so
+/// `https://host/derby/` and `https://host/derby` are saved, and matched
+/// against the server targets, as the same server.
+pub fn normalize_server_url(server_url: &str) -> String {
+    server_url.trim().trim_end_matches('/').to_string()
+}
+
+fn check_server_targets(targets: &[ServerTarget]) -> Result<(), String> {
+    for (index, target) in targets.iter().enumerate() {
+        if target.name.trim().is_empty() {
+            return Err(format!("the server at {} needs a name", target.url));
+        }
+        if targets[..index]
+            .iter()
+            .any(|other| other.name.eq_ignore_ascii_case(&target.name))
+        {
+            return Err(format!("there are two servers called {}", target.name));
+        }
+        check_server_url(&target.url).map_err(|message| format!("{} {}", target.name, message))?;
+    }
+    Ok(())
+}
+
+/// Profile names pick out a directory and a keyring entry each, so they
+/// must be there and must differ by more than case.
+fn check_profile_names(profiles: &[Profile], fields: &mut Vec<FieldError>) {
+    for (index, profile) in profiles.iter().enumerate() {
+        let message = if profile.name.trim().is_empty() {
+            "a profile needs a name".to_string()
+        } else if profiles[..index]
+            .iter()
+            .any(|other| other.name.eq_ignore_ascii_case(&profile.name))
+        {
+            format!("there are two profiles called {}", profile.name)
+        } else {
+            continue;
+        };
+        fields.push(FieldError {
+            field: "profileName".to_string(),
+            message,
+        });
+        return;
+    }
+}
+
+/// Derby Live keys are URL safe Base64 of a fixed length, so anything else
+/// was mistyped or pasted with something extra.
+fn check_key(key: &str, expected_len: usize) -> Result<(), String> {
+    if let Some(c) = key
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && *c != '-' && *c != '_')
+    {
+        return Err(format!("contains {:?}, which never appears in a key", c));
+    }
+    if key.len() != expected_len {
+        return Err(format!(
+            "is {} characters long, but keys are {}",
+            key.len(),
+            expected_len
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,204 @@
+//! Stages a race in a GrandPrix-format database: a roster and race chart up
+//! front, then heat results written one heat at a time, the way the timer
+//! would. Pointing the synchronizer at the file exercises the watcher,
+//! debouncing and upload without a track or GrandPrix itself.
+
+use log::info;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rusqlite::{params, Connection, OpenFlags};
+use std::{collections::HashMap, path::Path, time::Duration};
+
+/// Creates the tables and views the GrandPrix reader expects.
+pub const SCHEMA: &str = include_str!("grandprix_schema.sql");
+
+const FIRST_NAMES: &[&str] = &[
+    "Avery", "Blake", "Casey", "Drew", "Emery", "Finley", "Gray", "Harper", "Jordan", "Kai",
+    "Logan", "Morgan", "Parker", "Quinn", "Riley", "Sawyer",
+];
+const LAST_NAMES: &[&str] = &[
+    "Adams", "Baker", "Clark", "Davis", "Evans", "Frost", "Garcia", "Hughes", "Irwin", "Jones",
+    "Kim", "Lopez", "Moore", "Nguyen", "Ortiz", "Patel",
+];
+
+#[derive(Debug, Clone)]
+pub struct SimulatorOptions {
+    /// One class, rank and round is created for each name
+    pub classes: Vec<String>,
+    pub racers_per_class: usize,
+    pub lane_count: usize,
+    /// How long `run` waits before each heat
+    pub heat_interval: Duration,
+    /// Seeds the roster and times, so the same options stage the same race
+    pub seed: u64,
+}
+
+impl Default for SimulatorOptions {
+    fn default() -> Self {
+        SimulatorOptions {
+            classes: vec![
+                "Tigers".to_string(),
+                "Wolves".to_string(),
+                "Bears".to_string(),
+            ],
+            racers_per_class: 6,
+            lane_count: 4,
+            heat_interval: Duration::from_secs(10),
+            seed: 0,
+        }
+    }
+}
+
+pub struct Simulator {
+    conn: Connection,
+    heat_numbers: Vec<i32>,
+    next_heat: usize,
+    heat_interval: Duration,
+    /// Each car's typical time, so the same cars keep winning
+    base_seconds: HashMap<i32, f64>,
+    rng: StdRng,
+}
+
+impl Simulator {
+    /// Writes a new database at `path` with every heat scheduled and none
+    /// run. Fails rather than touching a file that already has the tables.
+    pub fn create(path: &Path, options: &SimulatorOptions) -> Result<Simulator, rusqlite::Error> {
+        info!(target: "simulator", "create: {:?}", path);
+        let mut conn = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+        )?;
+        conn.execute_batch(SCHEMA)?;
+
+        let mut rng = StdRng::seed_from_u64(options.seed);
+        let mut base_seconds = HashMap::new();
+        let mut heat_numbers = Vec::new();
+        let mut racer_id = 0;
+        let mut heat_number = 0;
+
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO RaceInfo (ItemKey, ItemValue) VALUES ('lane_count', ?1)",
+            params![options.lane_count.to_string()],
+        )?;
+        for (index, class) in options.classes.iter().enumerate() {
+            let class_id = index as i32 + 1;
+            tx.execute(
+                "INSERT INTO Classes (ClassID, Class) VALUES (?1, ?2)",
+                params![class_id, class],
+            )?;
+            tx.execute(
+                "INSERT INTO Ranks (RankID, Rank, ClassID) VALUES (?1, ?2, ?1)",
+                params![class_id, class],
+            )?;
+            tx.execute(
+                "INSERT INTO Rounds (RoundID, Round, ClassID) VALUES (?1, 1, ?1)",
+                params![class_id],
+            )?;
+
+            let mut racer_ids = Vec::new();
+            for seat in 0..options.racers_per_class {
+                racer_id += 1;
+                tx.execute(
+                    "INSERT INTO RegistrationInfo
+                        (RacerID, CarNumber, LastName, FirstName, ClassID, RankID)
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+                    params![
+                        racer_id,
+                        class_id * 100 + seat as i32 + 1,
+                        LAST_NAMES[rng.gen_range(0..LAST_NAMES.len())],
+                        FIRST_NAMES[rng.gen_range(0..FIRST_NAMES.len())],
+                        class_id
+                    ],
+                )?;
+                base_seconds.insert(racer_id, rng.gen_range(2.9..3.6));
+                racer_ids.push(racer_id);
+            }
+
+            // Every car runs once in each lane the class can fill
+            let racer_count = racer_ids.len();
+            let lanes = options.lane_count.min(racer_count);
+            for heat in 0..racer_count {
+                heat_number += 1;
+                heat_numbers.push(heat_number);
+                for lane in 0..lanes {
+                    tx.execute(
+                        "INSERT INTO RaceChart (ClassID, RoundID, Heat, Lane, RacerID)
+                            VALUES (?1, ?1, ?2, ?3, ?4)",
+                        params![
+                            class_id,
+                            heat_number,
+                            lane as i32 + 1,
+                            racer_ids[(heat + lane) % racer_count]
+                        ],
+                    )?;
+                }
+            }
+        }
+        tx.commit()?;
+
+        Ok(Simulator {
+            conn,
+            heat_numbers,
+            next_heat: 0,
+            heat_interval: options.heat_interval,
+            base_seconds,
+            rng,
+        })
+    }
+
+    pub fn remaining_heats(&self) -> usize {
+        self.heat_numbers.len() - self.next_heat
+    }
+
+    /// Writes times and places for the next heat in one transaction, as the
+    /// timer would when the cars cross the line. Returns the heat number, or
+    /// `None` once every heat has been run.
+    pub fn run_heat(&mut self) -> Result<Option<i32>, rusqlite::Error> {
+        let Some(&heat_number) = self.heat_numbers.get(self.next_heat) else {
+            return Ok(None);
+        };
+
+        let mut lanes = {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT ResultID, RacerID FROM RaceChart WHERE Heat = ?1")?;
+            let rows = stmt.query_map(params![heat_number], |row| {
+                Ok((row.get::<_, i32>(0)?, row.get::<_, i32>(1)?))
+            })?;
+            let mut lanes = Vec::new();
+            for row in rows {
+                let (result_id, racer_id) = row?;
+                let base = self.base_seconds.get(&racer_id).copied().unwrap_or(3.3);
+                let seconds = base + self.rng.gen_range(-0.03..0.03);
+                lanes.push((result_id, (seconds * 1000.0).round() / 1000.0));
+            }
+            lanes
+        };
+        lanes.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+        let tx = self.conn.transaction()?;
+        for (place, (result_id, seconds)) in lanes.iter().enumerate() {
+            tx.execute(
+                "UPDATE RaceChart
+                    SET FinishTime = ?1, FinishPlace = ?2, Completed = DATETIME('now')
+                    WHERE ResultID = ?3",
+                params![seconds, place as i32 + 1, result_id],
+            )?;
+        }
+        tx.commit()?;
+
+        self.next_heat += 1;
+        info!(target: "simulator", "run_heat: heat {}", heat_number);
+        Ok(Some(heat_number))
+    }
+
+    /// Runs every remaining heat, waiting the heat interval before each.
+    pub async fn run(mut self) -> Result<(), rusqlite::Error> {
+        loop {
+            tokio::time::sleep(self.heat_interval).await;
+            if self.run_heat()?.is_none() {
+                return Ok(());
+            }
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::{fmt, io::Read, path::Path};
+
+use crate::database::{
+    self, has_table, table_columns, Class, Racer, RacerHeat, Rank, Round, TrackConfig,
+};
+use crate::derbynet;
+
+/// Everything read from the timing software in a single sync.
+#[derive(Debug, Default)]
+pub struct RaceData {
+    pub racers: Vec<Racer>,
+    pub racer_heats: Vec<RacerHeat>,
+    pub structure: RaceStructure,
+}
+
+/// How the event is laid out, as opposed to who is racing in it.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RaceStructure {
+    pub classes: Vec<Class>,
+    pub ranks: Vec<Rank>,
+    pub track: TrackConfig,
+    pub rounds: Vec<Round>,
+}
+
+/// A timing software database that can be read into the shapes `/api/data`
+/// expects. Supporting a new timer means implementing this and adding a
+/// `SourceKind` for it; the synchronizer only ever sees the trait.
+pub trait RaceDataSource: Send {
+    fn kind(&self) -> SourceKind;
+    fn collect_data(&self) -> Result<RaceData, SourceError>;
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SourceKind {
+    #[default]
+    Auto,
+    GrandPrix,
+    DerbyNet,
+}
+
+/// Parses the names settings.json uses, ignoring case, for `--source`.
+impl std::str::FromStr for SourceKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(SourceKind::Auto),
+            "grandprix" => Ok(SourceKind::GrandPrix),
+            "derbynet" => Ok(SourceKind::DerbyNet),
+            _ => Err(format!("expected auto, grandPrix or derbyNet, not {}", s)),
+        }
+    }
+}
+
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+// Columns each adapter selects. A database missing any of them comes from a
+// version of the timing software this app does not understand.
+const GRANDPRIX_COLUMNS: &[(&str, &[&str])] = &[
+    (
+        "qryRoster",
+        &[
+            "RacerID",
+            "LastName",
+            "FirstName",
+            "CarNumber",
+            "CarName",
+            "Class",
+            "Rank",
+        ],
+    ),
+    ("RegistrationInfo", &["RacerID", "CarNumber", "RankID"]),
+    (
+        "RaceChart",
+        &[
+            "ResultID",
+            "RacerID",
+            "ClassID",
+            "Heat",
+            "Lane",
+            "FinishTime",
+            "FinishPlace",
+            "Completed",
+        ],
+    ),
+    ("Classes", &["ClassID", "Class"]),
+    ("Ranks", &["RankID", "Rank"]),
+];
+const DERBYNET_COLUMNS: &[(&str, &[&str])] = &[
+    (
+        "RegistrationInfo",
+        &[
+            "racerid",
+            "lastname",
+            "firstname",
+            "carnumber",
+            "carname",
+            "classid",
+            "rankid",
+        ],
+    ),
+    (
+        "RaceChart",
+        &[
+            "resultid",
+            "racerid",
+            "roundid",
+            "heat",
+            "lane",
+            "finishtime",
+            "finishplace",
+            "completed",
+            "masterheat",
+        ],
+    ),
+    ("Rounds", &["roundid", "classid"]),
+    ("Classes", &["classid", "class"]),
+    ("Ranks", &["rankid", "rank"]),
+];
+
+/// What `probe` found in a chosen database file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DetectedSource {
+    pub kind: SourceKind,
+    pub version: Option<String>,
+}
+
+impl fmt::Display for DetectedSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.kind {
+            SourceKind::Auto => "Unknown",
+            SourceKind::GrandPrix => "GrandPrix Race Manager",
+            SourceKind::DerbyNet => "DerbyNet",
+        };
+        match &self.version {
+            Some(version) => write!(f, "{} (schema {})", name, version),
+            None => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SourceError {
+    DatabaseError(rusqlite::Error),
+    FileError(std::io::Error),
+    NotSqlite,
+    UnrecognizedSource,
+    UnsupportedVersion(DetectedSource, String),
+    MismatchedSource(SourceKind, DetectedSource),
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceError::DatabaseError(e) => write!(f, "DatabaseError: {}", e),
+            SourceError::FileError(e) => write!(f, "FileError: {}", e),
+            SourceError::NotSqlite => {
+                write!(f, "NotSqlite: the chosen file is not a SQLite database")
+            }
+            SourceError::UnrecognizedSource => {
+                write!(f, "UnrecognizedSource: not a supported timing database")
+            }
+            SourceError::UnsupportedVersion(detected, missing) => write!(
+                f,
+                "UnsupportedVersion: {} is not supported (missing {})",
+                detected, missing
+            ),
+            SourceError::MismatchedSource(expected, detected) => write!(
+                f,
+                "MismatchedSource: settings expect {:?} but the database is {}",
+                expected, detected
+            ),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for SourceError {
+    fn from(e: rusqlite::Error) -> Self {
+        SourceError::DatabaseError(e)
+    }
+}
+
+pub fn open(kind: SourceKind, path: &Path) -> Result<Box<dyn RaceDataSource>, SourceError> {
+    let kind = match kind {
+        SourceKind::Auto => detect(path)?,
+        kind => kind,
+    };
+    info!(target: "sync", "source::open: {:?} {:?}", kind, path);
+
+    match kind {
+        SourceKind::GrandPrix => Ok(Box::new(database::Client::new(path.to_path_buf())?)),
+        SourceKind::DerbyNet => Ok(Box::new(derbynet::Client::new(path.to_path_buf())?)),
+        SourceKind::Auto => Err(SourceError::UnrecognizedSource),
+    }
+}
+
+pub fn detect(path: &Path) -> Result<SourceKind, SourceError> {
+    probe(path).map(|detected| detected.kind)
+}
+
+/// Probes a database file before it is used: checks that it is SQLite,
+/// works out which timing software produced it, and confirms the tables and
+/// columns the matching adapter reads are all there.
+pub fn probe(path: &Path) -> Result<DetectedSource, SourceError> {
+    let mut header = [0u8; 16];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => SourceError::NotSqlite,
+            _ => SourceError::FileError(e),
+        })?;
+    if &header != SQLITE_HEADER {
+        return Err(SourceError::NotSqlite);
+    }
+
+    let conn = database::open_read_only(path)?;
+
+    let detected = if has_table(&conn, "qryRoster")? && has_table(&conn, "RaceChart")? {
+        let user_version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        DetectedSource {
+            kind: SourceKind::GrandPrix,
+            version: (user_version > 0).then(|| user_version.to_string()),
+        }
+    } else if has_table(&conn, "RaceInfo")? && has_table(&conn, "Rounds")? {
+        let version = conn
+            .query_row(
+                "SELECT itemvalue FROM RaceInfo WHERE itemkey = 'schema'",
+                [],
+                |row| row.get::<_, String>(0),
+            )
+            .ok();
+        DetectedSource {
+            kind: SourceKind::DerbyNet,
+            version,
+        }
+    } else {
+        return Err(SourceError::UnrecognizedSource);
+    };
+
+    let required_columns = match detected.kind {
+        SourceKind::DerbyNet => DERBYNET_COLUMNS,
+        _ => GRANDPRIX_COLUMNS,
+    };
+    for (table, columns) in required_columns {
+        let present = table_columns(&conn, table)?;
+        for column in *columns {
+            if !present.iter().any(|c| c.eq_ignore_ascii_case(column)) {
+                return Err(SourceError::UnsupportedVersion(
+                    detected,
+                    format!("{}.{}", table, column),
+                ));
+            }
+        }
+    }
+
+    info!(target: "sync", "source::probe: {:?} is {}", path, detected);
+
+    Ok(detected)
+}
+
+/// Probes `path` and checks it against the source kind chosen in settings.
+pub fn check(kind: SourceKind, path: &Path) -> Result<DetectedSource, SourceError> {
+    let detected = probe(path)?;
+    if kind != SourceKind::Auto && kind != detected.kind {
+        return Err(SourceError::MismatchedSource(kind, detected));
+    }
+
+    Ok(detected)
+}
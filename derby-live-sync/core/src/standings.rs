@@ -0,0 +1,224 @@
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, collections::HashMap};
+
+use crate::database::{HeatStatus, RoundKind};
+use crate::source::RaceData;
+use crate::validate;
+
+// What a DNF or DQ counts as: the slowest reading the timer can give, so a
+// run that did not count can never help a racer's time.
+const PENALTY_SECONDS: f64 = validate::MAX_TIMER_SECONDS;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ScoringMethod {
+    /// Lowest average finish time wins
+    #[default]
+    AverageTime,
+    /// Places earn points, first place earning one point per lane
+    Points,
+    /// Racers who reach a later round finish ahead of those knocked out
+    /// earlier, then by average time within that round
+    Elimination,
+}
+
+/// Parses the names settings.json uses, ignoring case, for `--scoring`.
+impl std::str::FromStr for ScoringMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "averagetime" => Ok(ScoringMethod::AverageTime),
+            "points" => Ok(ScoringMethod::Points),
+            "elimination" => Ok(ScoringMethod::Elimination),
+            _ => Err(format!(
+                "expected averageTime, points or elimination, not {}",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Standing {
+    pub racer_id: i32,
+    pub car_number: i32,
+    pub group: String,
+    pub heats_run: i32,
+    pub average_seconds: Option<f64>,
+    pub best_seconds: Option<f64>,
+    pub points: i32,
+    pub rank_in_group: i32,
+}
+
+#[derive(Debug, Default)]
+struct Tally {
+    times: Vec<f64>,
+    points: i32,
+    // (round number, times in that round) for the furthest round reached
+    furthest_round: Option<(i32, Vec<f64>)>,
+}
+
+/// Computes the leaderboard for every racer in the roster, ranked within
+/// their group. Completed runs and byes count towards times and points as
+/// they finished; DNFs and DQs count as a run at the penalty time and earn no
+/// points. Scheduled runs and runs waiting to be rerun do not count yet.
+pub fn compute(data: &RaceData, method: ScoringMethod) -> Vec<Standing> {
+    let round_numbers: HashMap<i32, i32> = data
+        .structure
+        .rounds
+        .iter()
+        .map(|round| {
+            // A grand final always outranks any den final, whatever the
+            // timer numbered it
+            let number = match round.kind {
+                RoundKind::GrandFinal => i32::MAX,
+                _ => round.round_number,
+            };
+            (round.round_id, number)
+        })
+        .collect();
+
+    let lane_count = lane_count(data);
+    let mut tallies: HashMap<i32, Tally> = HashMap::new();
+    for racer_heat in &data.racer_heats {
+        let tally = tallies.entry(racer_heat.racer_id).or_default();
+        let seconds = match racer_heat.status {
+            HeatStatus::Completed | HeatStatus::Bye => {
+                if let Some(place) = racer_heat.finish_place {
+                    tally.points += (lane_count - place + 1).max(0);
+                }
+                racer_heat.finish_seconds
+            }
+            HeatStatus::Dnf | HeatStatus::Dq => Some(PENALTY_SECONDS),
+            HeatStatus::Scheduled | HeatStatus::Rerun => None,
+        };
+        let Some(seconds) = seconds else {
+            continue;
+        };
+        tally.times.push(seconds);
+
+        let round_number = racer_heat
+            .round_id
+            .and_then(|round_id| round_numbers.get(&round_id).copied())
+            .unwrap_or(1);
+        match &mut tally.furthest_round {
+            Some((furthest, times)) if *furthest == round_number => times.push(seconds),
+            Some((furthest, _)) if *furthest > round_number => {}
+            _ => tally.furthest_round = Some((round_number, vec![seconds])),
+        }
+    }
+
+    let mut standings: Vec<(Standing, Tally)> = data
+        .racers
+        .iter()
+        .map(|racer| {
+            let tally = tallies.remove(&racer.racer_id).unwrap_or_default();
+            let standing = Standing {
+                racer_id: racer.racer_id,
+                car_number: racer.car_number,
+                group: racer.group.clone(),
+                heats_run: tally.times.len() as i32,
+                average_seconds: average(&tally.times),
+                best_seconds: tally
+                    .times
+                    .iter()
+                    .copied()
+                    .filter(|&seconds| validate::is_real_time(seconds))
+                    .reduce(f64::min),
+                points: tally.points,
+                rank_in_group: 0,
+            };
+            (standing, tally)
+        })
+        .collect();
+
+    standings.sort_by(|(a, a_tally), (b, b_tally)| {
+        a.group
+            .cmp(&b.group)
+            .then_with(|| compare(method, a, a_tally, b, b_tally))
+            .then_with(|| a.car_number.cmp(&b.car_number))
+    });
+
+    // Standard competition ranking, so tied racers share a rank
+    let mut group_start = 0;
+    for i in 0..standings.len() {
+        if i > 0 && standings[i - 1].0.group != standings[i].0.group {
+            group_start = i;
+        }
+
+        let (standing, tally) = &standings[i];
+        let rank_in_group = match i.checked_sub(1).filter(|&j| j >= group_start) {
+            Some(j)
+                if compare(method, &standings[j].0, &standings[j].1, standing, tally)
+                    == Ordering::Equal =>
+            {
+                standings[j].0.rank_in_group
+            }
+            _ => (i - group_start + 1) as i32,
+        };
+        standings[i].0.rank_in_group = rank_in_group;
+    }
+
+    standings
+        .into_iter()
+        .map(|(standing, _)| standing)
+        .collect()
+}
+
+fn compare(
+    method: ScoringMethod,
+    a: &Standing,
+    a_tally: &Tally,
+    b: &Standing,
+    b_tally: &Tally,
+) -> Ordering {
+    match method {
+        ScoringMethod::AverageTime => compare_times(a.average_seconds, b.average_seconds),
+        ScoringMethod::Points => b
+            .points
+            .cmp(&a.points)
+            .then_with(|| compare_times(a.average_seconds, b.average_seconds)),
+        ScoringMethod::Elimination => {
+            let a_round = a_tally.furthest_round.as_ref();
+            let b_round = b_tally.furthest_round.as_ref();
+            b_round
+                .map(|(number, _)| *number)
+                .cmp(&a_round.map(|(number, _)| *number))
+                .then_with(|| {
+                    compare_times(
+                        a_round.and_then(|(_, times)| average(times)),
+                        b_round.and_then(|(_, times)| average(times)),
+                    )
+                })
+        }
+    }
+}
+
+/// Faster first, with racers who have no time yet at the bottom
+fn compare_times(a: Option<f64>, b: Option<f64>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Lanes on the track, falling back to the highest lane raced on when the
+/// timer does not record a lane count
+fn lane_count(data: &RaceData) -> i32 {
+    match data.structure.track.lane_count {
+        0 => data
+            .racer_heats
+            .iter()
+            .map(|rh| rh.lane_number)
+            .max()
+            .unwrap_or_default(),
+        lane_count => lane_count,
+    }
+}
+
+fn average(times: &[f64]) -> Option<f64> {
+    (!times.is_empty()).then(|| times.iter().sum::<f64>() / times.len() as f64)
+}
@@ -0,0 +1,646 @@
+extern crate notify;
+extern crate rusqlite;
+
+use log::info;
+use notify::{Config, PollWatcher, RecursiveMode, Watcher};
+use std::{
+    fmt,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use crate::delta::{SyncLedger, FULL_RESYNC_INTERVAL};
+use crate::events::{EventSink, SyncEvent};
+use crate::lane_stats;
+use crate::payload::{RequestData, ResponseData, INVALID_EVENT_KEY};
+use crate::source::{self, SourceError, SourceKind};
+use crate::standings::{self, ScoringMethod};
+use crate::upload_queue::{Backoff, QueuedUpload, UploadQueue};
+use crate::validate::{self, Severity};
+
+/// A database written more often than the debounce is synced after this
+/// many debounce periods rather than never.
+const MAX_DEBOUNCE_FACTOR: u32 = 5;
+
+#[derive(Clone)]
+pub struct SyncState {
+    events: Arc<dyn EventSink>,
+    watched_path: PathBuf,
+    api_key: String,
+    event_key: String,
+    server_url: String,
+    options: SyncOptions,
+}
+
+/// How a sync behaves, as opposed to where it reads from and uploads to.
+#[derive(Debug, Clone)]
+pub struct SyncOptions {
+    pub source_kind: SourceKind,
+    pub debounce: Duration,
+    pub scoring_method: ScoringMethod,
+    pub warnings_block_sync: bool,
+}
+
+#[derive(Debug)]
+pub enum SyncCreationError {
+    MissingWatchedPath,
+    MissingApiKey,
+    MissingEventKey,
+    MissingServerUrl,
+    NonExistentWatchedPath,
+    UnsupportedDatabase(SourceError),
+}
+
+impl fmt::Display for SyncCreationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncCreationError::MissingWatchedPath => write!(f, "no database selected"),
+            SyncCreationError::MissingApiKey => write!(f, "no API key in settings"),
+            SyncCreationError::MissingEventKey => write!(f, "no event key in settings"),
+            SyncCreationError::MissingServerUrl => write!(f, "no server URL in settings"),
+            SyncCreationError::NonExistentWatchedPath => {
+                write!(f, "the selected database no longer exists")
+            }
+            SyncCreationError::UnsupportedDatabase(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl SyncState {
+    pub fn try_new(
+        events: Arc<dyn EventSink>,
+        watched_path: Option<PathBuf>,
+        api_key: Option<String>,
+        event_key: Option<String>,
+        server_url: Option<String>,
+        mut options: SyncOptions,
+    ) -> Result<Self, SyncCreationError> {
+        let watched_path = watched_path.ok_or(SyncCreationError::MissingWatchedPath)?;
+        let api_key = api_key.ok_or(SyncCreationError::MissingApiKey)?;
+        let event_key = event_key.ok_or(SyncCreationError::MissingEventKey)?;
+        let server_url = server_url.ok_or(SyncCreationError::MissingServerUrl)?;
+
+        if !watched_path.exists() {
+            return Err(SyncCreationError::NonExistentWatchedPath);
+        }
+
+        let detected = source::check(options.source_kind, &watched_path)
+            .map_err(SyncCreationError::UnsupportedDatabase)?;
+        options.source_kind = detected.kind;
+
+        Ok(Self {
+            events,
+            watched_path,
+            api_key,
+            event_key,
+            server_url,
+            options,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct Synchronizer {
+    running: Arc<AtomicBool>,
+    sync_state: Arc<SyncState>,
+    watcher: Arc<Mutex<Option<PollWatcher>>>,
+    ledger: Arc<Mutex<SyncLedger>>,
+    queue: Arc<Mutex<UploadQueue>>,
+    queue_notify: Arc<tokio::sync::Notify>,
+}
+
+enum SyncMessage {
+    SyncEvent(notify::Result<notify::Event>),
+    SyncScan(notify::poll::ScanEvent),
+}
+
+#[derive(Debug)]
+pub enum SyncError {
+    DatabaseError(SourceError),
+    UploadError(reqwest::Error),
+    NotifyError(notify::Error),
+    AuthError(String),
+    EventKeyError(String),
+    /// Any other 4xx: the request itself is wrong, usually the server URL
+    ClientError(String),
+    RateLimitError(Option<Duration>),
+    ServerError(String),
+    ValidationError(usize, usize),
+}
+
+impl SyncError {
+    /// Errors that will keep failing until the operator changes settings, so
+    /// retrying the upload is pointless.
+    fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            SyncError::AuthError(_) | SyncError::EventKeyError(_) | SyncError::ClientError(_)
+        )
+    }
+}
+
+impl fmt::Display for SyncError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncError::DatabaseError(e) => write!(f, "{}", e),
+            SyncError::UploadError(e) => write!(f, "UploadError: {}", e),
+            SyncError::NotifyError(e) => write!(f, "NotifyError: {}", e),
+            SyncError::AuthError(e) => write!(f, "AuthError: {}, check the API key", e),
+            SyncError::EventKeyError(e) => write!(f, "EventKeyError: {}, check the event key", e),
+            SyncError::ClientError(e) => write!(f, "ClientError: {}, check the server URL", e),
+            SyncError::RateLimitError(_) => write!(f, "RateLimitError: too many uploads"),
+            SyncError::ServerError(e) => write!(f, "ServerError: {}", e),
+            SyncError::ValidationError(errors, warnings) => write!(
+                f,
+                "ValidationError: not uploading, found {} errors and {} warnings",
+                errors, warnings
+            ),
+        }
+    }
+}
+
+struct Uploader {
+    api_key: String,
+    server_url: String,
+}
+
+impl Synchronizer {
+    pub fn with_queue(sync_state: SyncState, queue: UploadQueue) -> Synchronizer {
+        Synchronizer {
+            running: Arc::new(AtomicBool::new(false)),
+            sync_state: Arc::new(sync_state),
+            watcher: Arc::new(Mutex::new(None)),
+            ledger: Arc::new(Mutex::new(SyncLedger::default())),
+            queue: Arc::new(Mutex::new(queue)),
+            queue_notify: Arc::new(tokio::sync::Notify::new()),
+        }
+    }
+
+    async fn run_sync(
+        sync_state: &SyncState,
+        ledger: &Mutex<SyncLedger>,
+        queue: &Mutex<UploadQueue>,
+        queue_notify: &tokio::sync::Notify,
+    ) -> Result<(), SyncError> {
+        info!(target: "sync", "run_sync");
+
+        // Reading may wait on the timing software's write lock, so keep it off
+        // the async workers.
+        let source_kind = sync_state.options.source_kind;
+        let watched_path = sync_state.watched_path.clone();
+        let collected = tokio::task::spawn_blocking(move || {
+            source::open(source_kind, &watched_path).and_then(|source| {
+                info!(target: "sync", "run_sync: collecting from {:?}", source.kind());
+                source.collect_data()
+            })
+        })
+        .await
+        .expect("failed to join collect_data");
+        let data = match collected {
+            Ok(data) => data,
+            Err(e) => {
+                sync_state.events.emit(SyncEvent::SyncError(e.to_string()));
+                return Err(SyncError::DatabaseError(e));
+            }
+        };
+
+        info!(target: "sync", "run_sync: {} racers & {} racer heats", data.racers.len(), data.racer_heats.len());
+
+        // Worked out before validation, since heats that fail it are often
+        // what the race committee is looking into
+        sync_state
+            .events
+            .emit(SyncEvent::LaneStatsUpdated(lane_stats::compute(&data)));
+
+        // Reported every time so the UI clears issues that have been fixed
+        let report = validate::check(&data);
+        let blocked = report.blocks_sync(sync_state.options.warnings_block_sync);
+        let (errors, warnings) = (
+            report.count(Severity::Error),
+            report.count(Severity::Warning),
+        );
+        sync_state
+            .events
+            .emit(SyncEvent::ValidationReported(report));
+        if blocked {
+            let e = SyncError::ValidationError(errors, warnings);
+            sync_state.events.emit(SyncEvent::SyncError(e.to_string()));
+            return Err(e);
+        }
+
+        let standings = standings::compute(&data, sync_state.options.scoring_method);
+
+        let delta = {
+            let mut ledger_locked = ledger.lock().unwrap();
+            let delta = ledger_locked.diff(data, standings);
+            // The queue delivers in order and survives restarts, so queued
+            // rows count as sent when diffing the next change.
+            ledger_locked.commit(&delta);
+            delta
+        };
+        if delta.is_empty() {
+            info!(target: "sync", "run_sync: no changes");
+            return Ok(());
+        }
+
+        let queue_depth = {
+            let mut queue_locked = queue.lock().unwrap();
+            queue_locked.push(QueuedUpload {
+                event_key: sync_state.event_key.clone(),
+                delta,
+            });
+            queue_locked.len()
+        };
+        sync_state
+            .events
+            .emit(SyncEvent::UploadQueueUpdated(queue_depth));
+        queue_notify.notify_one();
+
+        info!(target: "sync", "run_sync: complete");
+
+        Ok(())
+    }
+
+    async fn run_uploads(
+        sync_state: &SyncState,
+        running: &AtomicBool,
+        queue: &Mutex<UploadQueue>,
+        queue_notify: &tokio::sync::Notify,
+    ) {
+        let uploader = Uploader::new(sync_state.api_key.clone(), sync_state.server_url.clone());
+        let mut backoff = Backoff::default();
+
+        while running.load(Ordering::Relaxed) {
+            let next_upload = queue.lock().unwrap().front().cloned();
+            let Some(upload) = next_upload else {
+                let _ = tokio::time::timeout(Duration::from_secs(1), queue_notify.notified()).await;
+                continue;
+            };
+
+            if let Err(e) = uploader.upload(&upload).await {
+                if e.is_fatal() {
+                    Synchronizer::stop_uploads(sync_state, running, queue, &upload, &e);
+                    return;
+                }
+
+                let delay = match e {
+                    SyncError::RateLimitError(Some(retry_after)) => retry_after,
+                    _ => backoff.next_delay(),
+                };
+                info!(target: "sync", "run_uploads: retrying in {:?}", delay);
+                sync_state.events.emit(SyncEvent::SyncError(format!(
+                    "{} (retrying in {}s)",
+                    e,
+                    delay.as_secs().max(1)
+                )));
+                tokio::time::sleep(delay).await;
+                continue;
+            }
+
+            backoff.reset();
+            let queue_depth = {
+                let mut queue_locked = queue.lock().unwrap();
+                queue_locked.pop_front();
+                queue_locked.len()
+            };
+            sync_state
+                .events
+                .emit(SyncEvent::UploadQueueUpdated(queue_depth));
+
+            let delta = upload.delta;
+            let message = if delta.full_resync {
+                format!(
+                    "Synced {} racers & {} racer heats (full resync)",
+                    delta.racers.len(),
+                    delta.racer_heats.len()
+                )
+            } else {
+                format!(
+                    "Synced {} changed racers & {} changed racer heats",
+                    delta.racers.len() + delta.removed_racer_ids.len(),
+                    delta.racer_heats.len() + delta.removed_result_ids.len()
+                )
+            };
+            sync_state.events.emit(SyncEvent::SyncUpdated(message));
+        }
+    }
+
+    fn stop_uploads(
+        sync_state: &SyncState,
+        running: &AtomicBool,
+        queue: &Mutex<UploadQueue>,
+        upload: &QueuedUpload,
+        error: &SyncError,
+    ) {
+        info!(target: "sync", "stop_uploads: {}", error);
+
+        if let SyncError::EventKeyError(_) = error {
+            // Everything queued for this key would be rejected too. The next
+            // start does a full resync, so nothing is lost by dropping them.
+            let queue_depth = {
+                let mut queue_locked = queue.lock().unwrap();
+                queue_locked.remove_event(&upload.event_key);
+                queue_locked.len()
+            };
+            sync_state
+                .events
+                .emit(SyncEvent::UploadQueueUpdated(queue_depth));
+        }
+
+        running.store(false, Ordering::Relaxed);
+        sync_state
+            .events
+            .emit(SyncEvent::SyncError(error.to_string()));
+        sync_state.events.emit(SyncEvent::SyncStopped);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    pub fn stop(&self) {
+        self.set_running(false);
+        // The thread it sends to exits once it sees the flag, and the next
+        // start makes a watcher that sends to that start's thread
+        *self.watcher.lock().unwrap() = None;
+        self.sync_state.events.emit(SyncEvent::SyncStopped);
+    }
+
+    /// Starts watching and uploading. Must be called from within a Tokio
+    /// runtime, which the sync and upload tasks are spawned onto.
+    pub fn start(&self) -> Result<(), SyncError> {
+        if self.is_running() {
+            return Ok(());
+        }
+
+        self.set_running(true);
+        info!(target: "sync", "start_sync");
+
+        // Standard channel for notify
+        let (std_tx, std_rx) = std::sync::mpsc::channel();
+        // Async channel for sync. Holding a single message means a burst of
+        // changes during a sync collapses into one pending follow-up sync.
+        let (async_tx, mut async_rx) = tokio::sync::mpsc::channel::<()>(1);
+
+        let sync_state_clone = self.sync_state.clone();
+        let std_running_clone = self.running.clone();
+        let async_running_clone = self.running.clone();
+        let ledger_clone = self.ledger.clone();
+        let queue_clone = self.queue.clone();
+        let queue_notify_clone = self.queue_notify.clone();
+
+        let sync_state_clone_for_uploads = self.sync_state.clone();
+        let running_clone_for_uploads = self.running.clone();
+        let queue_clone_for_uploads = self.queue.clone();
+        let queue_notify_clone_for_uploads = self.queue_notify.clone();
+        tokio::spawn(async move {
+            Synchronizer::run_uploads(
+                &sync_state_clone_for_uploads,
+                &running_clone_for_uploads,
+                &queue_clone_for_uploads,
+                &queue_notify_clone_for_uploads,
+            )
+            .await;
+        });
+
+        // Start watching and add the watcher to self for broader lifetime
+        self.try_create_watcher(std_tx)?;
+        self.start_watch()?;
+
+        sync_state_clone.events.emit(SyncEvent::SyncStarted);
+        sync_state_clone.events.emit(SyncEvent::UploadQueueUpdated(
+            self.queue.lock().unwrap().len(),
+        ));
+
+        std::thread::spawn(move || {
+            while std_running_clone.load(Ordering::Relaxed) {
+                match std_rx.recv_timeout(Duration::from_secs(1)) {
+                    Ok(SyncMessage::SyncEvent(Ok(event))) => {
+                        info!(target: "sync", "Received on std_rx: {:?}", event);
+                        // A full channel already has a sync pending
+                        let _ = async_tx.try_send(());
+                    }
+                    Ok(SyncMessage::SyncEvent(Err(e))) => {
+                        info!(target: "sync", "Received on std_rx: {:?}", e);
+                    }
+                    Ok(SyncMessage::SyncScan(scan_event)) => {
+                        info!(target: "sync", "Received on std_rx: {:?}", scan_event);
+                    }
+                    Err(e) => {
+                        info!(target: "sync", "std_rx error: {:?}", e);
+                    }
+                }
+            }
+        });
+
+        tokio::spawn(async move {
+            let debounce = sync_state_clone.options.debounce;
+            // Syncs only ever run from this task, so at most one is in flight
+            let mut pending = true;
+            // The database can sit untouched through a break or the awards,
+            // and the periodic full resync must not wait for the next heat
+            let resync_check_interval = FULL_RESYNC_INTERVAL / 10;
+            let mut resync_check = tokio::time::interval_at(
+                tokio::time::Instant::now() + resync_check_interval,
+                resync_check_interval,
+            );
+            resync_check.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+            while async_running_clone.load(Ordering::Relaxed) {
+                if !pending {
+                    tokio::select! {
+                        received = tokio::time::timeout(Duration::from_secs(1), async_rx.recv()) => {
+                            match received {
+                                Ok(Some(())) => {}
+                                Ok(None) => break,
+                                Err(_) => continue,
+                            }
+                        }
+                        _ = resync_check.tick() => {
+                            if !ledger_clone.lock().unwrap().full_resync_due() {
+                                continue;
+                            }
+                            info!(target: "sync", "full resync due with no changes");
+                        }
+                    }
+                }
+                pending = false;
+
+                // Wait for the timing software to finish writing before
+                // reading, but not forever if it never stops writing
+                let deadline = tokio::time::Instant::now() + debounce * MAX_DEBOUNCE_FACTOR;
+                loop {
+                    let quiet_until = (tokio::time::Instant::now() + debounce).min(deadline);
+                    match tokio::time::timeout_at(quiet_until, async_rx.recv()).await {
+                        Ok(Some(())) if tokio::time::Instant::now() < deadline => {
+                            info!(target: "sync", "debounce: change during quiet period");
+                        }
+                        Ok(Some(())) => {
+                            info!(target: "sync", "debounce: still changing, syncing anyway");
+                            break;
+                        }
+                        _ => break,
+                    }
+                }
+
+                if !async_running_clone.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                let _ = Synchronizer::run_sync(
+                    &sync_state_clone,
+                    &ledger_clone,
+                    &queue_clone,
+                    &queue_notify_clone,
+                )
+                .await;
+            }
+        });
+
+        Ok(())
+    }
+
+    fn start_watch(&self) -> Result<(), SyncError> {
+        let watcher_clone = Arc::clone(&self.watcher);
+
+        if let Some(watcher) = watcher_clone.lock().unwrap().as_mut() {
+            let path = self.sync_state.watched_path.clone();
+
+            info!(target: "sync", "start_watch: {:?}", path);
+
+            watcher
+                .watch(path.as_ref(), RecursiveMode::NonRecursive)
+                .map_err(SyncError::NotifyError)?;
+        }
+
+        Ok(())
+    }
+
+    fn try_create_watcher(
+        &self,
+        tx: std::sync::mpsc::Sender<SyncMessage>,
+    ) -> Result<(), SyncError> {
+        let watcher = Arc::clone(&self.watcher);
+        let mut watcher_locked = watcher.lock().unwrap();
+
+        let tx_clone = tx.clone();
+
+        if watcher_locked.is_none() {
+            info!(target: "sync", "try_create_watcher");
+            // The poll watcher only tracks modification times to the second,
+            // so a heat saved in the same second as the last change would be
+            // missed without comparing contents as well.
+            let config = Config::default()
+                .with_poll_interval(Duration::from_secs(1))
+                .with_compare_contents(true);
+
+            let new_watcher = PollWatcher::with_initial_scan(
+                // Sends fail once the sync has stopped and is no longer
+                // listening, which is nothing to act on
+                move |watch_event| {
+                    let _ = tx_clone.send(SyncMessage::SyncEvent(watch_event));
+                },
+                config,
+                move |scan_event| {
+                    let _ = tx.send(SyncMessage::SyncScan(scan_event));
+                },
+            )
+            .map_err(SyncError::NotifyError)?;
+
+            *watcher_locked = Some(new_watcher);
+        }
+
+        Ok(())
+    }
+
+    fn set_running(&self, running: bool) {
+        self.running.store(running, Ordering::Relaxed);
+    }
+}
+
+/// `api/data` relative to the server URL, which may include the path the
+/// site is mounted under, with or without a trailing slash. A URL that does
+/// not parse is passed on as it is for reqwest to report.
+fn api_url(server_url: &str) -> String {
+    let base = format!("{}/", server_url.trim_end_matches('/'));
+    reqwest::Url::parse(&base)
+        .and_then(|base| base.join("api/data"))
+        .map(String::from)
+        .unwrap_or_else(|_| format!("{}api/data", base))
+}
+
+impl Uploader {
+    fn new(api_key: String, server_url: String) -> Uploader {
+        Uploader {
+            api_key,
+            server_url,
+        }
+    }
+
+    async fn upload(&self, upload: &QueuedUpload) -> Result<(), SyncError> {
+        let client = reqwest::Client::new();
+        let request_data = RequestData::from(upload);
+        info!(target: "sync", "upload: event_key:{:?}, server_url:{:?}", upload.event_key, self.server_url);
+
+        let url = api_url(&self.server_url);
+
+        let resp = client
+            .post(url)
+            .header("x-api-key", &self.api_key)
+            .json(&request_data)
+            .send()
+            .await
+            .map_err(SyncError::UploadError)?;
+
+        let status = resp.status();
+        let retry_after = resp
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .map(Duration::from_secs);
+        let body = resp.text().await.map_err(SyncError::UploadError)?;
+        let response_data: ResponseData = serde_json::from_str(&body).unwrap_or_default();
+
+        info!(target: "sync", "upload: status:{}, response:{:?}", status, response_data);
+
+        Uploader::check_response(status, retry_after, response_data)
+    }
+
+    fn check_response(
+        status: reqwest::StatusCode,
+        retry_after: Option<Duration>,
+        response_data: ResponseData,
+    ) -> Result<(), SyncError> {
+        let message = response_data
+            .message
+            .or(response_data.error)
+            .unwrap_or_else(|| status.to_string());
+
+        match status {
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => {
+                Err(SyncError::AuthError(message))
+            }
+            reqwest::StatusCode::TOO_MANY_REQUESTS => Err(SyncError::RateLimitError(retry_after)),
+            // A timeout is worth retrying; any other 4xx would fail the same
+            // way every time and hold up the uploads queued behind it
+            status
+                if status.is_client_error() && status != reqwest::StatusCode::REQUEST_TIMEOUT =>
+            {
+                Err(SyncError::ClientError(message))
+            }
+            status if !status.is_success() => Err(SyncError::ServerError(message)),
+            _ => match response_data.status.as_deref() {
+                Some("ok") => Ok(()),
+                Some("error") if message == INVALID_EVENT_KEY => {
+                    Err(SyncError::EventKeyError(message))
+                }
+                _ => Err(SyncError::ServerError(message)),
+            },
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use log::info;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, path::PathBuf, time::Duration};
+
+use crate::config_dir;
+use crate::delta::Delta;
+
+pub const QUEUE_FILE: &str = "upload_queue.json";
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedUpload {
+    pub event_key: String,
+    pub delta: Delta,
+}
+
+/// Payloads waiting to be uploaded, oldest first. The queue is written to
+/// disk after every change so pending uploads survive an app restart.
+#[derive(Debug)]
+pub struct UploadQueue {
+    path: PathBuf,
+    uploads: VecDeque<QueuedUpload>,
+}
+
+impl UploadQueue {
+    /// The queue for one profile, so profiles syncing at once do not write
+    /// over each other's.
+    pub fn for_profile(profile: &str) -> UploadQueue {
+        UploadQueue::load_from(config_dir::profile_dir(profile).join(QUEUE_FILE))
+    }
+
+    pub fn load_from(path: PathBuf) -> UploadQueue {
+        let uploads = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        let queue = UploadQueue { path, uploads };
+        info!(target: "sync", "UploadQueue::load_from: {} pending uploads", queue.len());
+        queue
+    }
+
+    pub fn len(&self) -> usize {
+        self.uploads.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.uploads.is_empty()
+    }
+
+    pub fn front(&self) -> Option<&QueuedUpload> {
+        self.uploads.front()
+    }
+
+    pub fn push(&mut self, upload: QueuedUpload) {
+        self.uploads.push_back(upload);
+        self.persist();
+    }
+
+    pub fn pop_front(&mut self) -> Option<QueuedUpload> {
+        let upload = self.uploads.pop_front();
+        self.persist();
+        upload
+    }
+
+    pub fn remove_event(&mut self, event_key: &str) {
+        self.uploads.retain(|upload| upload.event_key != event_key);
+        self.persist();
+    }
+
+    fn persist(&self) {
+        let result = if self.uploads.is_empty() {
+            match std::fs::remove_file(&self.path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            }
+        } else {
+            serde_json::to_string(&self.uploads)
+                .map_err(std::io::Error::from)
+                .and_then(|contents| config_dir::write_atomic(&self.path, contents.as_bytes()))
+        };
+
+        if let Err(e) = result {
+            info!(target: "sync", "UploadQueue::persist: failed to write {:?}: {}", self.path, e);
+        }
+    }
+}
+
+/// Exponential backoff with jitter, so several laptops that lost the same
+/// gym Wi-Fi do not all retry at the same instant.
+#[derive(Debug, Default)]
+pub struct Backoff {
+    attempt: u32,
+}
+
+impl Backoff {
+    pub fn next_delay(&mut self) -> Duration {
+        let ceiling = INITIAL_RETRY_DELAY
+            .saturating_mul(2u32.saturating_pow(self.attempt))
+            .min(MAX_RETRY_DELAY);
+        self.attempt = self.attempt.saturating_add(1);
+
+        let half = ceiling / 2;
+        half + rand::thread_rng().gen_range(Duration::ZERO..=half)
+    }
+
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
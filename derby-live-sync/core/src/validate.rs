@@ -0,0 +1,167 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+use crate::database::HeatStatus;
+use crate::source::RaceData;
+
+// What the timer records for a lane it never saw a car cross: either nothing
+// at all or its maximum reading.
+const UNTIMED_SECONDS: f64 = 0.0;
+pub(crate) const MAX_TIMER_SECONDS: f64 = 99.999;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Severity {
+    /// Suspicious, but safe to show on the display
+    Warning,
+    /// The data is inconsistent and would show the wrong thing
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Issue {
+    pub severity: Severity,
+    pub message: String,
+    pub racer_id: Option<i32>,
+    pub result_id: Option<i32>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationReport {
+    pub issues: Vec<Issue>,
+}
+
+impl ValidationReport {
+    pub fn count(&self, severity: Severity) -> usize {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == severity)
+            .count()
+    }
+
+    /// Errors always block a sync; warnings only when the operator asks.
+    pub fn blocks_sync(&self, warnings_block_sync: bool) -> bool {
+        self.count(Severity::Error) > 0 || (warnings_block_sync && !self.issues.is_empty())
+    }
+
+    fn push(
+        &mut self,
+        severity: Severity,
+        racer_id: Option<i32>,
+        result_id: Option<i32>,
+        message: String,
+    ) {
+        self.issues.push(Issue {
+            severity,
+            message,
+            racer_id,
+            result_id,
+        });
+    }
+}
+
+/// Looks for data the timing software should not have produced, before any
+/// of it is uploaded.
+pub fn check(data: &RaceData) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    let mut racers_by_car: HashMap<i32, Vec<i32>> = HashMap::new();
+    for racer in &data.racers {
+        racers_by_car
+            .entry(racer.car_number)
+            .or_default()
+            .push(racer.racer_id);
+    }
+    let mut shared_cars: Vec<(&i32, &Vec<i32>)> = racers_by_car
+        .iter()
+        .filter(|(_, racer_ids)| racer_ids.len() > 1)
+        .collect();
+    shared_cars.sort_unstable();
+    for (car_number, racer_ids) in shared_cars {
+        report.push(
+            Severity::Warning,
+            racer_ids.first().copied(),
+            None,
+            format!(
+                "Car {} is registered to {} racers",
+                car_number,
+                racer_ids.len()
+            ),
+        );
+    }
+
+    let roster: HashSet<i32> = data.racers.iter().map(|r| r.racer_id).collect();
+    let mut lanes_taken = HashSet::new();
+    for racer_heat in &data.racer_heats {
+        let heat = format!("{} heat {}", racer_heat.group, racer_heat.heat_number);
+        let racer_id = Some(racer_heat.racer_id);
+        let result_id = Some(racer_heat.result_id);
+
+        if !roster.contains(&racer_heat.racer_id) {
+            report.push(
+                Severity::Error,
+                racer_id,
+                result_id,
+                format!(
+                    "{} has car {} in lane {}, but that racer is not in the roster",
+                    heat, racer_heat.car_number, racer_heat.lane_number
+                ),
+            );
+        }
+
+        let lane = (
+            racer_heat.round_id,
+            &racer_heat.group,
+            racer_heat.heat_number,
+            racer_heat.lane_number,
+        );
+        // A run thrown out to be raced again shares its lane with the rerun
+        if racer_heat.status != HeatStatus::Rerun && !lanes_taken.insert(lane) {
+            report.push(
+                Severity::Error,
+                racer_id,
+                result_id,
+                format!(
+                    "{} has more than one car in lane {}",
+                    heat, racer_heat.lane_number
+                ),
+            );
+        }
+
+        match (racer_heat.finish_seconds, racer_heat.finish_place) {
+            (Some(seconds), _) if !is_real_time(seconds) => {
+                report.push(
+                    Severity::Warning,
+                    racer_id,
+                    result_id,
+                    format!(
+                        "{} lane {} has a finish time of {:.3}",
+                        heat, racer_heat.lane_number, seconds
+                    ),
+                );
+            }
+            (None, Some(place)) => {
+                report.push(
+                    Severity::Warning,
+                    racer_id,
+                    result_id,
+                    format!(
+                        "{} lane {} placed {} without a finish time",
+                        heat, racer_heat.lane_number, place
+                    ),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    report
+}
+
+/// Whether a finish time was actually measured, rather than filled in by the
+/// timer for a lane it never saw a car cross.
+pub fn is_real_time(seconds: f64) -> bool {
+    seconds > UNTIMED_SECONDS && seconds < MAX_TIMER_SECONDS
+}
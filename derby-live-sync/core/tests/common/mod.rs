@@ -0,0 +1,74 @@
+// Each test crate compiles its own copy of this module and uses only some of it.
+#![allow(dead_code)]
+
+use derby_live_core::events::SyncEvent;
+use derby_live_core::simulator;
+use derby_live_core::source::{self, RaceData, SourceKind};
+use derby_live_core::standings::ScoringMethod;
+use derby_live_core::synchronize::{SyncOptions, SyncState, Synchronizer};
+use derby_live_core::upload_queue::UploadQueue;
+use rusqlite::Connection;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+pub const API_KEY: &str = "test-api-key";
+pub const EVENT_KEY: &str = "test-event";
+
+/// Writes the fixture GrandPrix event to `grandprix.sqlite` in `dir`.
+pub fn grandprix_database(dir: &Path) -> PathBuf {
+    let path = dir.join("grandprix.sqlite");
+    let conn = Connection::open(&path).expect("failed to create fixture database");
+    conn.execute_batch(simulator::SCHEMA)
+        .and_then(|_| conn.execute_batch(include_str!("../fixtures/grandprix.sql")))
+        .expect("failed to load fixture database");
+    path
+}
+
+/// Writes the fixture DerbyNet event, schema included, to `derbynet.sqlite`
+/// in `dir`.
+pub fn derbynet_database(dir: &Path) -> PathBuf {
+    let path = dir.join("derbynet.sqlite");
+    let conn = Connection::open(&path).expect("failed to create fixture database");
+    conn.execute_batch(include_str!("../fixtures/derbynet.sql"))
+        .expect("failed to load fixture database");
+    path
+}
+
+/// Reads the database at `path` the way a sync would, detecting its source.
+pub fn collect(path: &Path) -> RaceData {
+    source::open(SourceKind::Auto, path)
+        .and_then(|source| source.collect_data())
+        .expect("failed to collect fixture data")
+}
+
+/// Builds a synchronizer that reads `database_path` and uploads to
+/// `server_url` with the test keys, queueing at `queue_path`, along with the
+/// receiving end of its events.
+pub fn synchronizer(
+    database_path: &Path,
+    server_url: &str,
+    queue_path: PathBuf,
+) -> (Synchronizer, UnboundedReceiver<SyncEvent>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let sync_state = SyncState::try_new(
+        Arc::new(tx),
+        Some(database_path.to_path_buf()),
+        Some(API_KEY.to_string()),
+        Some(EVENT_KEY.to_string()),
+        Some(server_url.to_string()),
+        SyncOptions {
+            source_kind: SourceKind::Auto,
+            debounce: Duration::from_millis(100),
+            scoring_method: ScoringMethod::AverageTime,
+            warnings_block_sync: false,
+        },
+    )
+    .expect("failed to create sync state");
+    let queue = UploadQueue::load_from(queue_path);
+
+    (Synchronizer::with_queue(sync_state, queue), rx)
+}
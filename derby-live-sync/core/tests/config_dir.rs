@@ -0,0 +1,102 @@
+use derby_live_core::config_dir;
+
+#[test]
+fn environment_variable_overrides_the_platform_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    std::env::set_var(config_dir::ENV_VAR, dir.path());
+
+    assert_eq!(config_dir::path(), dir.path());
+    assert_eq!(
+        config_dir::file("settings.json"),
+        dir.path().join("settings.json")
+    );
+}
+
+#[test]
+fn migrate_copies_old_files_without_overwriting_new_ones() {
+    let cwd = tempfile::tempdir().unwrap();
+    let config = tempfile::tempdir().unwrap();
+    let to = config.path().join("derby-live-sync");
+    let old_settings = r#"{"serverUrl":"https://derby.example.com","eventKey":"pack-42"}"#;
+    std::fs::write(cwd.path().join("settings.json"), old_settings).unwrap();
+    std::fs::write(cwd.path().join("upload_queue.json"), "old queue").unwrap();
+    std::fs::create_dir_all(&to).unwrap();
+    std::fs::write(to.join("upload_queue.json"), "new queue").unwrap();
+
+    config_dir::migrate(cwd.path(), &to);
+
+    assert_eq!(
+        std::fs::read_to_string(to.join("settings.json")).unwrap(),
+        old_settings
+    );
+    // Copied, so an older version run from the same place still works
+    assert!(cwd.path().join("settings.json").exists());
+    assert_eq!(
+        std::fs::read_to_string(to.join("upload_queue.json")).unwrap(),
+        "new queue"
+    );
+}
+
+#[test]
+fn migrate_drops_the_api_key_from_the_old_settings() {
+    let cwd = tempfile::tempdir().unwrap();
+    let config = tempfile::tempdir().unwrap();
+    let to = config.path().join("derby-live-sync");
+    std::fs::write(
+        cwd.path().join("settings.json"),
+        r#"{"apiKey":"secret-api-key","serverUrl":"https://derby.example.com"}"#,
+    )
+    .unwrap();
+
+    config_dir::migrate(cwd.path(), &to);
+
+    let copied = std::fs::read_to_string(to.join("settings.json")).unwrap();
+    assert!(copied.contains("secret-api-key"));
+    let old = std::fs::read_to_string(cwd.path().join("settings.json")).unwrap();
+    assert!(!old.contains("secret-api-key"), "{}", old);
+    assert!(old.contains("https://derby.example.com"), "{}", old);
+}
+
+#[test]
+fn migrate_leaves_other_programs_settings_alone() {
+    let cwd = tempfile::tempdir().unwrap();
+    let config = tempfile::tempdir().unwrap();
+    let to = config.path().join("derby-live-sync");
+    std::fs::write(cwd.path().join("settings.json"), r#"{"theme":"dark"}"#).unwrap();
+    std::fs::write(cwd.path().join("upload_queue.json"), "[]").unwrap();
+
+    config_dir::migrate(cwd.path(), &to);
+
+    assert!(!to.exists());
+    assert!(cwd.path().join("settings.json").exists());
+}
+
+#[test]
+fn write_atomic_replaces_the_file_and_cleans_up() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("nested").join("settings.json");
+
+    config_dir::write_atomic(&path, b"first").unwrap();
+    config_dir::write_atomic(&path, b"second").unwrap();
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "second");
+    let names: Vec<_> = std::fs::read_dir(path.parent().unwrap())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(names, vec!["settings.json"]);
+}
+
+#[test]
+fn profile_dirs_escape_names_that_are_not_file_safe() {
+    let dir = tempfile::tempdir().unwrap();
+
+    assert_eq!(
+        config_dir::profile_dir_in(dir.path(), "Pack 42"),
+        dir.path().join("profiles").join("Pack 42")
+    );
+    assert_eq!(
+        config_dir::profile_dir_in(dir.path(), "../Cubs/Webelos"),
+        dir.path().join("profiles").join("%2E%2E%2FCubs%2FWebelos")
+    );
+}
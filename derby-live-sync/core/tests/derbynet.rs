@@ -0,0 +1,115 @@
+mod common;
+
+use derby_live_core::database::{HeatStatus, RoundKind};
+use derby_live_core::source::{self, RaceData, SourceKind};
+
+fn heat_of(data: &RaceData, result_id: i32) -> i32 {
+    data.racer_heats
+        .iter()
+        .find(|rh| rh.result_id == result_id)
+        .map(|rh| rh.heat_number)
+        .expect("no such result")
+}
+
+#[test]
+fn probe_detects_derbynet() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = common::derbynet_database(dir.path());
+
+    let detected = source::probe(&path).unwrap();
+
+    assert_eq!(detected.kind, SourceKind::DerbyNet);
+}
+
+#[test]
+fn reads_racers_from_lowercase_columns() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let data = common::collect(&common::derbynet_database(dir.path()));
+
+    let mut racers = data.racers.clone();
+    racers.sort_by_key(|r| r.car_number);
+    let summary: Vec<_> = racers
+        .iter()
+        .map(|r| {
+            (
+                r.car_number,
+                r.first_name.as_str(),
+                r.group.as_str(),
+                r.rank.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (101, "Avery", "Lions", "Lion"),
+            (102, "Blake", "Lions", "Lion"),
+            (201, "Casey", "Tigers", "Tiger"),
+            (202, "Drew", "Tigers", "Tiger"),
+        ]
+    );
+    assert_eq!(racers[1].car_name, None);
+}
+
+#[test]
+fn numbers_heats_by_masterheat() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let data = common::collect(&common::derbynet_database(dir.path()));
+
+    // Both rounds number their own heats 1 and 2; masterheat interleaves them
+    assert_eq!(heat_of(&data, 1), 1);
+    assert_eq!(heat_of(&data, 3), 2);
+    assert_eq!(heat_of(&data, 5), 3);
+    assert_eq!(heat_of(&data, 7), 4);
+    // No masterheat assigned yet, so the round's own heat number is used
+    assert_eq!(heat_of(&data, 9), 1);
+}
+
+#[test]
+fn takes_the_group_from_the_round_class() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let data = common::collect(&common::derbynet_database(dir.path()));
+
+    let finals: Vec<_> = data
+        .racer_heats
+        .iter()
+        .filter(|rh| rh.round_id == Some(3))
+        .collect();
+    assert_eq!(finals.len(), 2);
+    assert!(finals.iter().all(|rh| rh.group == "Grand Final"));
+    assert!(finals.iter().all(|rh| rh.status == HeatStatus::Scheduled));
+    assert!(data
+        .racer_heats
+        .iter()
+        .filter(|rh| rh.round_id == Some(1))
+        .all(|rh| rh.group == "Lions" && rh.status == HeatStatus::Completed));
+}
+
+#[test]
+fn reads_the_event_structure() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let data = common::collect(&common::derbynet_database(dir.path()));
+
+    let structure = &data.structure;
+    assert_eq!(structure.track.lane_count, 3);
+    assert_eq!(structure.track.unused_lanes, vec![3]);
+    let classes: Vec<_> = structure.classes.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(classes, vec!["Lions", "Tigers", "Grand Final"]);
+    let rounds: Vec<_> = structure
+        .rounds
+        .iter()
+        .map(|r| (r.round_id, r.kind))
+        .collect();
+    assert_eq!(
+        rounds,
+        vec![
+            (1, RoundKind::Preliminary),
+            (2, RoundKind::Preliminary),
+            (3, RoundKind::GrandFinal),
+        ]
+    );
+}
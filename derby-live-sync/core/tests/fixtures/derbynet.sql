@@ -0,0 +1,82 @@
+-- A small DerbyNet event on a three-lane track with lane 3 switched off. The
+-- Lions and Tigers have raced their preliminaries, run interleaved so that
+-- masterheat differs from each round's own heat numbers, and a grand final
+-- drawn from both dens has been scheduled but not yet run. DerbyNet has its
+-- own lowercase schema, so unlike grandprix.sql this creates the tables too.
+
+CREATE TABLE Classes (
+    classid INTEGER PRIMARY KEY,
+    class VARCHAR(200),
+    sortorder INTEGER
+);
+CREATE TABLE Ranks (
+    rankid INTEGER PRIMARY KEY,
+    rank VARCHAR(200),
+    classid INTEGER,
+    sortorder INTEGER
+);
+CREATE TABLE RegistrationInfo (
+    racerid INTEGER PRIMARY KEY,
+    carnumber INTEGER,
+    carname VARCHAR(200),
+    lastname VARCHAR(200),
+    firstname VARCHAR(200),
+    classid INTEGER,
+    rankid INTEGER,
+    exclude INTEGER DEFAULT 0
+);
+CREATE TABLE Rounds (
+    roundid INTEGER PRIMARY KEY,
+    round INTEGER,
+    classid INTEGER,
+    charttype INTEGER,
+    phase INTEGER
+);
+CREATE TABLE RaceChart (
+    resultid INTEGER PRIMARY KEY,
+    classid INTEGER,
+    roundid INTEGER,
+    heat INTEGER,
+    lane INTEGER,
+    racerid INTEGER,
+    finishtime DOUBLE,
+    finishplace INTEGER,
+    completed DATETIME,
+    ignoretime INTEGER DEFAULT 0,
+    masterheat INTEGER DEFAULT 0
+);
+CREATE TABLE RaceInfo (
+    raceinfoid INTEGER PRIMARY KEY,
+    itemkey VARCHAR(20),
+    itemvalue VARCHAR(200)
+);
+
+INSERT INTO Classes VALUES (1, 'Lions', 1), (2, 'Tigers', 2), (3, 'Grand Final', 3);
+INSERT INTO Ranks VALUES (1, 'Lion', 1, 1), (2, 'Tiger', 2, 1);
+INSERT INTO RaceInfo VALUES
+    (1, 'schema', '7'),
+    (2, 'lane_count', '3'),
+    (3, 'unused-lane-mask', '4');
+
+INSERT INTO RegistrationInfo VALUES
+    (1, 101, 'Roar', 'Adams', 'Avery', 1, 1, 0),
+    (2, 102, NULL, 'Baker', 'Blake', 1, 1, 0),
+    (3, 201, 'Stripe', 'Clark', 'Casey', 2, 2, 0),
+    (4, 202, 'Pounce', 'Davis', 'Drew', 2, 2, 0);
+
+-- The grand final is its own class in DerbyNet; its round says so even
+-- though every racer in it is registered in the Lions or the Tigers.
+INSERT INTO Rounds VALUES (1, 1, 1, 0, 0), (2, 1, 2, 0, 0), (3, 2, 3, 0, 0);
+
+INSERT INTO RaceChart VALUES
+    (1, 1, 1, 1, 1, 1, 3.100, 1, '2024-03-02 10:00:00', 0, 1),
+    (2, 1, 1, 1, 2, 2, 3.200, 2, '2024-03-02 10:00:00', 0, 1),
+    (3, 2, 2, 1, 1, 3, 3.050, 1, '2024-03-02 10:02:00', 0, 2),
+    (4, 2, 2, 1, 2, 4, 3.300, 2, '2024-03-02 10:02:00', 0, 2),
+    (5, 1, 1, 2, 1, 2, 3.150, 1, '2024-03-02 10:04:00', 0, 3),
+    (6, 1, 1, 2, 2, 1, 3.250, 2, '2024-03-02 10:04:00', 0, 3),
+    (7, 2, 2, 2, 1, 4, 3.120, 1, '2024-03-02 10:06:00', 0, 4),
+    (8, 2, 2, 2, 2, 3, 3.180, 2, '2024-03-02 10:06:00', 0, 4),
+    -- Not yet placed in the running order, so the round's heat number stands
+    (9, 3, 3, 1, 1, 1, NULL, NULL, NULL, 0, 0),
+    (10, 3, 3, 1, 2, 3, NULL, NULL, NULL, 0, 0);
@@ -0,0 +1,43 @@
+-- A small GrandPrix Race Manager event on a three-lane track: the Tigers
+-- have finished their heats, the Wolves are part way through theirs. Loaded
+-- on top of the simulator's schema.
+
+INSERT INTO Classes VALUES (1, 'Tigers'), (2, 'Wolves');
+INSERT INTO Ranks VALUES (1, 'Tiger', 1), (2, 'Wolf', 2);
+INSERT INTO RaceInfo VALUES (1, 'lane_count', '3');
+
+INSERT INTO RegistrationInfo VALUES
+    (1, 101, 'Adams', 'Avery', 'Lightning', 1, 1, 0),
+    (2, 102, 'Baker', 'Blake', 'Thunder', 1, 1, 0),
+    (3, 103, 'Clark', 'Casey', NULL, 1, 1, 0),
+    (4, 201, 'Davis', 'Drew', 'Comet', 2, 2, 0),
+    (5, 202, 'Evans', 'Emery', 'Rocket', 2, 2, 0),
+    (6, 203, 'Frost', 'Finley', NULL, 2, 2, 0);
+
+INSERT INTO Rounds VALUES (1, 1, 1), (2, 1, 2);
+
+INSERT INTO RaceChart
+    (ResultID, ClassID, RoundID, Heat, Lane, RacerID, FinishTime, FinishPlace, Completed)
+VALUES
+    (1, 1, 1, 1, 1, 1, 3.100, 1, '2024-03-02 10:00:00'),
+    (2, 1, 1, 1, 2, 2, 3.200, 2, '2024-03-02 10:00:00'),
+    (3, 1, 1, 1, 3, 3, 3.300, 3, '2024-03-02 10:00:00'),
+    (4, 1, 1, 2, 1, 2, 3.150, 1, '2024-03-02 10:02:00'),
+    (5, 1, 1, 2, 2, 3, 3.250, 2, '2024-03-02 10:02:00'),
+    (6, 1, 1, 2, 3, 1, 3.350, 3, '2024-03-02 10:02:00'),
+    (7, 1, 1, 3, 1, 3, 3.120, 1, '2024-03-02 10:04:00'),
+    (8, 1, 1, 3, 2, 1, 3.180, 2, '2024-03-02 10:04:00'),
+    -- The timer's maximum reading: the car never crossed the line
+    (9, 1, 1, 3, 3, 2, 99.999, 3, '2024-03-02 10:04:00'),
+    (10, 2, 2, 4, 1, 4, 3.000, 1, '2024-03-02 10:06:00'),
+    (11, 2, 2, 4, 2, 5, NULL, NULL, '2024-03-02 10:06:00'),
+    (12, 2, 2, 4, 3, 6, 3.400, 2, '2024-03-02 10:06:00'),
+    (13, 2, 2, 5, 1, 5, NULL, NULL, NULL),
+    (14, 2, 2, 5, 2, 6, NULL, NULL, NULL),
+    (15, 2, 2, 5, 3, 4, NULL, NULL, NULL),
+    (16, 2, 2, 6, 2, 4, NULL, NULL, NULL);
+
+-- Heat 2 was first run with car 102 in lane 1, then thrown out and rerun;
+-- the timer keeps the discarded run beside its replacement
+INSERT INTO RaceChart VALUES
+    (17, 1, 1, 2, 1, 2, 2.900, 1, '2024-03-02 10:01:00', 1);
@@ -0,0 +1,149 @@
+mod common;
+
+use derby_live_core::database::HeatStatus;
+use derby_live_core::source::{self, RaceData, SourceError, SourceKind};
+use derby_live_core::standings::{self, ScoringMethod};
+use derby_live_core::validate::{self, Severity};
+
+fn status_of(data: &RaceData, result_id: i32) -> HeatStatus {
+    data.racer_heats
+        .iter()
+        .find(|rh| rh.result_id == result_id)
+        .map(|rh| rh.status)
+        .expect("no such result")
+}
+
+#[test]
+fn probe_detects_grandprix() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = common::grandprix_database(dir.path());
+
+    let detected = source::probe(&path).unwrap();
+
+    assert_eq!(detected.kind, SourceKind::GrandPrix);
+}
+
+#[test]
+fn probe_rejects_files_that_are_not_sqlite() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("roster.csv");
+    std::fs::write(&path, "RacerID,CarNumber\n1,101\n").unwrap();
+
+    let result = source::probe(&path);
+
+    assert!(matches!(result, Err(SourceError::NotSqlite)));
+}
+
+#[test]
+fn probe_rejects_databases_missing_required_columns() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = common::grandprix_database(dir.path());
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute("ALTER TABLE RaceChart DROP COLUMN FinishPlace", [])
+        .unwrap();
+
+    let result = source::probe(&path);
+
+    match result {
+        Err(SourceError::UnsupportedVersion(_, missing)) => {
+            assert_eq!(missing, "RaceChart.FinishPlace")
+        }
+        other => panic!("expected UnsupportedVersion, got {:?}", other),
+    }
+}
+
+#[test]
+fn collect_data_reads_roster_heats_and_structure() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let data = common::collect(&common::grandprix_database(dir.path()));
+
+    assert_eq!(data.racers.len(), 6);
+    assert_eq!(data.racer_heats.len(), 17);
+    assert_eq!(data.structure.classes.len(), 2);
+    assert_eq!(data.structure.rounds.len(), 2);
+    assert_eq!(data.structure.track.lane_count, 3);
+    assert!(data.structure.track.unused_lanes.is_empty());
+}
+
+#[test]
+fn track_config_tolerates_out_of_range_lane_settings() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = common::grandprix_database(dir.path());
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "UPDATE RaceInfo SET ItemValue = '1000' WHERE ItemKey = 'lane_count';
+         INSERT INTO RaceInfo VALUES (2, 'unused-lane-mask', '-9223372036854775808');",
+    )
+    .unwrap();
+
+    let data = common::collect(&path);
+
+    assert_eq!(data.structure.track.lane_count, 64);
+    assert_eq!(data.structure.track.unused_lanes, vec![64]);
+}
+
+#[test]
+fn heat_results_carry_their_status() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let data = common::collect(&common::grandprix_database(dir.path()));
+
+    assert_eq!(status_of(&data, 1), HeatStatus::Completed);
+    assert_eq!(status_of(&data, 9), HeatStatus::Dnf);
+    assert_eq!(status_of(&data, 11), HeatStatus::Dnf);
+    assert_eq!(status_of(&data, 13), HeatStatus::Scheduled);
+    assert_eq!(status_of(&data, 16), HeatStatus::Bye);
+    assert_eq!(status_of(&data, 17), HeatStatus::Rerun);
+}
+
+#[test]
+fn standings_rank_runs_within_each_group() {
+    let dir = tempfile::tempdir().unwrap();
+    let data = common::collect(&common::grandprix_database(dir.path()));
+
+    let standings = standings::compute(&data, ScoringMethod::AverageTime);
+
+    let tigers: Vec<(i32, i32)> = standings
+        .iter()
+        .filter(|s| s.group == "Tigers")
+        .map(|s| (s.car_number, s.rank_in_group))
+        .collect();
+    // Car 102's 99.999 is a DNF, counted at the penalty time
+    assert_eq!(tigers, vec![(101, 1), (103, 2), (102, 3)]);
+
+    let wolves_leader = standings
+        .iter()
+        .find(|s| s.group == "Wolves" && s.rank_in_group == 1)
+        .unwrap();
+    assert_eq!(wolves_leader.car_number, 201);
+}
+
+#[test]
+fn validation_warns_about_sentinel_times() {
+    let dir = tempfile::tempdir().unwrap();
+    let data = common::collect(&common::grandprix_database(dir.path()));
+
+    let report = validate::check(&data);
+
+    assert_eq!(report.count(Severity::Error), 0);
+    assert_eq!(report.count(Severity::Warning), 1);
+    assert!(report.issues[0].message.contains("99.999"));
+    assert!(!report.blocks_sync(false));
+    assert!(report.blocks_sync(true));
+}
+
+#[test]
+fn validation_ignores_runs_thrown_out_for_a_rerun() {
+    let dir = tempfile::tempdir().unwrap();
+    let data = common::collect(&common::grandprix_database(dir.path()));
+
+    let report = validate::check(&data);
+
+    // Result 17 shares heat 2 lane 1 with the rerun that replaced it
+    assert!(report
+        .issues
+        .iter()
+        .all(|issue| issue.result_id != Some(17)));
+    assert!(!report.blocks_sync(false));
+}
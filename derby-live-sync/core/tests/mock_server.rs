@@ -0,0 +1,126 @@
+mod common;
+
+use derby_live_core::events::SyncEvent;
+use derby_live_core::mock_server::{self, MockServer};
+use derby_live_core::payload::ResponseData;
+use derby_live_core::settings::{Profile, API_KEY_LEN, DEFAULT_PROFILE, EVENT_KEY_LEN};
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+/// Starts a mock server on a free port and returns it with its base URL.
+async fn start() -> (MockServer, String) {
+    let server = MockServer::new(common::API_KEY.to_string(), common::EVENT_KEY.to_string());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(server.clone().serve(listener));
+    (server, url)
+}
+
+async fn post(url: &str, api_key: &str, body: serde_json::Value) -> (u16, ResponseData) {
+    let response = reqwest::Client::new()
+        .post(format!("{}/api/data", url))
+        .header("x-api-key", api_key)
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    let status = response.status().as_u16();
+    (status, response.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn rejects_unknown_api_and_event_keys() {
+    let (server, url) = start().await;
+
+    let (status, body) = post(
+        &url,
+        "wrong",
+        serde_json::json!({"event_key": common::EVENT_KEY}),
+    )
+    .await;
+    assert_eq!(status, 401);
+    assert_eq!(body.error.as_deref(), Some("Invalid API key"));
+
+    let (status, body) = post(
+        &url,
+        common::API_KEY,
+        serde_json::json!({"event_key": "other"}),
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(body.status.as_deref(), Some("error"));
+    assert_eq!(body.message.as_deref(), Some("Invalid event key"));
+
+    assert!(server.uploads().is_empty());
+}
+
+#[tokio::test]
+async fn shows_what_the_live_site_would_after_a_sync() {
+    let dir = tempfile::tempdir().unwrap();
+    let database_path = common::grandprix_database(dir.path());
+    let (server, url) = start().await;
+    let (synchronizer, mut rx) =
+        common::synchronizer(&database_path, &url, dir.path().join("upload_queue.json"));
+
+    synchronizer.start().unwrap();
+    tokio::time::timeout(Duration::from_secs(15), async {
+        while !matches!(rx.recv().await, Some(SyncEvent::SyncUpdated(_))) {}
+    })
+    .await
+    .expect("timed out waiting for the upload");
+    synchronizer.stop();
+
+    assert_eq!(server.uploads().len(), 1);
+    let view = server.live_view();
+    assert_eq!(view.heats.len(), 6);
+    assert_eq!(view.racers.len(), 6);
+    assert_eq!(view.standings.len(), 6);
+    // Heat 4 has a car with no place, so the live site still shows it as running
+    assert_eq!(view.current_heat.unwrap().heat_number, 4);
+    assert_eq!(view.next_heat.unwrap().heat_number, 5);
+
+    let page = reqwest::get(format!("{}/{}/heats", url, common::EVENT_KEY))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("Current heat: heat 4"), "{}", page);
+}
+
+#[tokio::test]
+async fn unknown_event_key_stops_the_sync() {
+    let dir = tempfile::tempdir().unwrap();
+    let database_path = common::grandprix_database(dir.path());
+    let server = MockServer::new(common::API_KEY.to_string(), "another-event".to_string());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(server.clone().serve(listener));
+    let (synchronizer, mut rx) =
+        common::synchronizer(&database_path, &url, dir.path().join("upload_queue.json"));
+
+    synchronizer.start().unwrap();
+    let error = tokio::time::timeout(Duration::from_secs(15), async {
+        loop {
+            if let Some(SyncEvent::SyncError(message)) = rx.recv().await {
+                return message;
+            }
+        }
+    })
+    .await
+    .expect("timed out waiting for the rejection");
+
+    assert!(error.contains("check the event key"), "{}", error);
+    assert!(server.uploads().is_empty());
+}
+
+#[test]
+fn generated_keys_pass_the_settings_checks() {
+    let profile = Profile {
+        api_key: Some(mock_server::generate_key(API_KEY_LEN)),
+        event_key: Some(mock_server::generate_key(EVENT_KEY_LEN)),
+        ..Profile::new(DEFAULT_PROFILE.to_string())
+    };
+
+    assert_eq!(profile.validate(), Ok(()));
+}
@@ -0,0 +1,44 @@
+use derby_live_core::secrets::SecretStore;
+
+#[test]
+fn fallback_file_round_trips_without_plain_text() {
+    let dir = tempfile::tempdir().unwrap();
+    let secrets = SecretStore::file_only(dir.path().to_path_buf());
+
+    assert_eq!(secrets.load_api_key(), None);
+    secrets.store_api_key("dl_live_0123456789").unwrap();
+
+    assert_eq!(
+        secrets.load_api_key().as_deref(),
+        Some("dl_live_0123456789")
+    );
+    for entry in std::fs::read_dir(dir.path()).unwrap() {
+        let contents = std::fs::read(entry.unwrap().path()).unwrap();
+        assert!(!contents
+            .windows(b"dl_live_0123456789".len())
+            .any(|window| window == b"dl_live_0123456789"));
+    }
+}
+
+#[test]
+fn storing_again_replaces_the_key() {
+    let dir = tempfile::tempdir().unwrap();
+    let secrets = SecretStore::file_only(dir.path().to_path_buf());
+
+    secrets.store_api_key("first").unwrap();
+    secrets.store_api_key("second").unwrap();
+
+    assert_eq!(secrets.load_api_key().as_deref(), Some("second"));
+}
+
+#[test]
+fn clearing_removes_the_fallback_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let secrets = SecretStore::file_only(dir.path().to_path_buf());
+    secrets.store_api_key("dl_live_0123456789").unwrap();
+
+    secrets.clear_api_key().unwrap();
+
+    assert_eq!(secrets.load_api_key(), None);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
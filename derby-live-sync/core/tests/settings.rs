@@ -0,0 +1,238 @@
+use derby_live_core::secrets::SecretStore;
+use derby_live_core::settings::{
+    AppSettings, Profile, SettingsError, DEFAULT_PROFILE, SETTINGS_VERSION,
+};
+
+const API_KEY: &str = "Jv3k_Qm8xZ2-pL7wRt5yNc1bHs9dFg4aUe6iOo0lKj2hGf8dSa3qWe5rTy7uIp9-";
+const EVENT_KEY: &str = "x7Yk2-Pq9Lm4_Rt8Wn3Zb6Vc1Hd5Jf0G";
+
+fn write_settings(dir: &std::path::Path, contents: &str) {
+    std::fs::write(dir.join("settings.json"), contents).unwrap();
+}
+
+#[test]
+fn unversioned_settings_move_the_api_key_out_of_the_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let secrets = SecretStore::file_only(dir.path().join("secrets"));
+    write_settings(
+        dir.path(),
+        &format!(
+            r#"{{"apiKey": "{}", "eventKey": "{}", "databasePath": null, "serverUrl": "http://localhost:4000"}}"#,
+            API_KEY, EVENT_KEY
+        ),
+    );
+
+    let app_settings = AppSettings::load_from(dir.path(), &secrets).unwrap();
+
+    assert_eq!(app_settings.version, SETTINGS_VERSION);
+    assert_eq!(app_settings.active().api_key.as_deref(), Some(API_KEY));
+    assert_eq!(app_settings.active().event_key.as_deref(), Some(EVENT_KEY));
+    assert_eq!(
+        secrets
+            .for_profile(DEFAULT_PROFILE)
+            .load_api_key()
+            .as_deref(),
+        Some(API_KEY)
+    );
+    assert_eq!(secrets.load_api_key(), None);
+    let rewritten = std::fs::read_to_string(dir.path().join("settings.json")).unwrap();
+    assert!(!rewritten.contains(API_KEY));
+    assert!(rewritten.contains(&format!("\"version\": {}", SETTINGS_VERSION)));
+}
+
+#[test]
+fn damaged_settings_are_reported_and_kept() {
+    let dir = tempfile::tempdir().unwrap();
+    let secrets = SecretStore::file_only(dir.path().join("secrets"));
+    let damaged = format!(
+        r#"{{"version": 2, "eventKey": "{}", "serverUrl": "#,
+        EVENT_KEY
+    );
+    write_settings(dir.path(), &damaged);
+
+    let result = AppSettings::load_from(dir.path(), &secrets);
+
+    assert!(matches!(result, Err(SettingsError::Malformed { .. })));
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("settings.json.bak")).unwrap(),
+        damaged
+    );
+}
+
+#[test]
+fn settings_from_a_newer_app_are_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let secrets = SecretStore::file_only(dir.path().join("secrets"));
+    write_settings(
+        dir.path(),
+        r#"{"version": 99, "serverUrl": "http://localhost:4000"}"#,
+    );
+
+    let result = AppSettings::load_from(dir.path(), &secrets);
+
+    assert_eq!(
+        result.unwrap_err(),
+        SettingsError::UnsupportedVersion { version: 99 }
+    );
+}
+
+#[test]
+fn validate_reports_every_bad_field() {
+    let app_settings = AppSettings {
+        profiles: vec![Profile {
+            api_key: Some(format!("{} ", API_KEY)),
+            event_key: Some("https://derby-live.fly.dev/events/abc".to_string()),
+            database_path: Some("/no/such/race.sqlite".into()),
+            server_url: "derby-live.fly.dev".to_string(),
+            ..Profile::new(DEFAULT_PROFILE.to_string())
+        }],
+        ..Default::default()
+    };
+
+    let fields: Vec<String> = match app_settings.validate() {
+        Err(SettingsError::Invalid { fields }) => fields.into_iter().map(|f| f.field).collect(),
+        other => panic!("expected Invalid, got {:?}", other),
+    };
+
+    assert_eq!(
+        fields,
+        vec!["serverUrl", "apiKey", "eventKey", "databasePath"]
+    );
+}
+
+#[test]
+fn validate_accepts_keys_in_the_shape_derby_live_issues() {
+    let profile = Profile {
+        api_key: Some(API_KEY.to_string()),
+        event_key: Some(EVENT_KEY.to_string()),
+        server_url: "https://derby-live.fly.dev".to_string(),
+        ..Profile::new(DEFAULT_PROFILE.to_string())
+    };
+
+    assert_eq!(profile.validate(), Ok(()));
+}
+
+#[test]
+fn validate_accepts_a_server_under_a_path_but_not_a_query() {
+    let mut profile = Profile {
+        server_url: "https://pack42.example/derby/".to_string(),
+        ..Profile::new(DEFAULT_PROFILE.to_string())
+    };
+    assert_eq!(profile.validate(), Ok(()));
+
+    profile.server_url = "https://pack42.example/derby?event=1".to_string();
+    assert!(profile.validate().is_err());
+}
+
+#[test]
+fn typed_server_urls_lose_their_trailing_slash() {
+    let app_settings = AppSettings::default();
+
+    assert_eq!(
+        app_settings.resolve_server(" https://pack42.example/derby/ "),
+        "https://pack42.example/derby"
+    );
+}
+
+#[test]
+fn saved_server_url_survives_a_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let secrets = SecretStore::file_only(dir.path().join("secrets"));
+    let mut app_settings = AppSettings::default();
+    app_settings.active_mut().server_url = "https://derby.pack42.example".to_string();
+    app_settings.write_to(dir.path()).unwrap();
+
+    let loaded = AppSettings::load_from(dir.path(), &secrets).unwrap();
+
+    assert_eq!(loaded.active().server_url, "https://derby.pack42.example");
+}
+
+#[test]
+fn version_2_settings_gain_the_built_in_servers() {
+    let dir = tempfile::tempdir().unwrap();
+    let secrets = SecretStore::file_only(dir.path().join("secrets"));
+    write_settings(
+        dir.path(),
+        r#"{"version": 2, "serverUrl": "https://derby.pack42.example"}"#,
+    );
+
+    let app_settings = AppSettings::load_from(dir.path(), &secrets).unwrap();
+
+    assert_eq!(
+        app_settings.active().server_url,
+        "https://derby.pack42.example"
+    );
+    assert_eq!(app_settings.server_target_name(), None);
+    assert_eq!(
+        app_settings.resolve_server("production"),
+        "https://derby-live.fly.dev"
+    );
+    assert_eq!(
+        app_settings.resolve_server("Local mock"),
+        "http://localhost:4001"
+    );
+}
+
+#[test]
+fn version_3_settings_become_the_default_profile() {
+    let dir = tempfile::tempdir().unwrap();
+    let secrets = SecretStore::file_only(dir.path().join("secrets"));
+    secrets.store_api_key(API_KEY).unwrap();
+    std::fs::write(dir.path().join("upload_queue.json"), "[]").unwrap();
+    write_settings(
+        dir.path(),
+        &format!(
+            r#"{{"version": 3, "eventKey": "{}", "databasePath": null, "serverUrl": "https://derby-live.fly.dev"}}"#,
+            EVENT_KEY
+        ),
+    );
+
+    let app_settings = AppSettings::load_from(dir.path(), &secrets).unwrap();
+
+    assert_eq!(app_settings.active_profile, DEFAULT_PROFILE);
+    assert_eq!(app_settings.profiles.len(), 1);
+    let profile = app_settings.active();
+    assert_eq!(profile.event_key.as_deref(), Some(EVENT_KEY));
+    assert_eq!(profile.api_key.as_deref(), Some(API_KEY));
+    assert_eq!(profile.server_url, "https://derby-live.fly.dev");
+    assert_eq!(secrets.load_api_key(), None);
+    assert!(!dir.path().join("upload_queue.json").exists());
+    assert!(dir
+        .path()
+        .join("profiles")
+        .join(DEFAULT_PROFILE)
+        .join("upload_queue.json")
+        .exists());
+}
+
+#[test]
+fn profiles_keep_their_own_keys_and_switch_by_name() {
+    let dir = tempfile::tempdir().unwrap();
+    let secrets = SecretStore::file_only(dir.path().join("secrets"));
+    let mut app_settings = AppSettings::default();
+    app_settings.add_profile("Pack 42").unwrap();
+    app_settings.active_mut().event_key = Some(EVENT_KEY.to_string());
+    app_settings.write_to(dir.path()).unwrap();
+    secrets
+        .for_profile("Pack 42")
+        .store_api_key(API_KEY)
+        .unwrap();
+
+    let mut loaded = AppSettings::load_from(dir.path(), &secrets).unwrap();
+
+    assert_eq!(loaded.active_profile, "Pack 42");
+    assert_eq!(loaded.active().api_key.as_deref(), Some(API_KEY));
+    loaded.switch_profile(DEFAULT_PROFILE).unwrap();
+    assert_eq!(loaded.active().api_key, None);
+    assert_eq!(loaded.active().event_key, None);
+    assert_eq!(
+        loaded.switch_profile("Pack 7"),
+        Err(SettingsError::UnknownProfile {
+            name: "Pack 7".to_string()
+        })
+    );
+    assert!(matches!(
+        loaded.add_profile("pack 42"),
+        Err(SettingsError::Invalid { .. })
+    ));
+}
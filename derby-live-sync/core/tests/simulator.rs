@@ -0,0 +1,107 @@
+mod common;
+
+use derby_live_core::database::HeatStatus;
+use derby_live_core::events::SyncEvent;
+use derby_live_core::mock_server::MockServer;
+use derby_live_core::simulator::{Simulator, SimulatorOptions};
+use derby_live_core::source::{self, SourceKind};
+use derby_live_core::validate;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+fn options() -> SimulatorOptions {
+    SimulatorOptions {
+        classes: vec!["Tigers".to_string(), "Wolves".to_string()],
+        racers_per_class: 3,
+        lane_count: 3,
+        heat_interval: Duration::from_millis(50),
+        seed: 7,
+    }
+}
+
+#[test]
+fn creates_a_schedule_the_grandprix_reader_accepts() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("simulated.sqlite");
+
+    let simulator = Simulator::create(&path, &options()).unwrap();
+
+    assert_eq!(source::probe(&path).unwrap().kind, SourceKind::GrandPrix);
+    assert_eq!(simulator.remaining_heats(), 6);
+    let data = common::collect(&path);
+    assert_eq!(data.racers.len(), 6);
+    assert_eq!(data.racer_heats.len(), 18);
+    assert_eq!(data.structure.track.lane_count, 3);
+    assert!(data
+        .racer_heats
+        .iter()
+        .all(|rh| rh.status == HeatStatus::Scheduled));
+}
+
+#[test]
+fn runs_one_heat_at_a_time_until_the_chart_is_done() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("simulated.sqlite");
+    let mut simulator = Simulator::create(&path, &options()).unwrap();
+
+    assert_eq!(simulator.run_heat().unwrap(), Some(1));
+
+    let data = common::collect(&path);
+    let mut places: Vec<i32> = data
+        .racer_heats
+        .iter()
+        .filter(|rh| rh.heat_number == 1)
+        .map(|rh| {
+            assert_eq!(rh.status, HeatStatus::Completed);
+            rh.finish_place.unwrap()
+        })
+        .collect();
+    places.sort();
+    assert_eq!(places, vec![1, 2, 3]);
+
+    while simulator.run_heat().unwrap().is_some() {}
+    assert_eq!(simulator.remaining_heats(), 0);
+    let data = common::collect(&path);
+    assert!(validate::check(&data).issues.is_empty());
+}
+
+#[tokio::test]
+async fn simulated_heats_reach_the_server() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("simulated.sqlite");
+    let simulator = Simulator::create(&path, &options()).unwrap();
+    let heats = simulator.remaining_heats();
+
+    let server = MockServer::new(common::API_KEY.to_string(), common::EVENT_KEY.to_string());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(server.clone().serve(listener));
+
+    let (synchronizer, mut rx) =
+        common::synchronizer(&path, &url, dir.path().join("upload_queue.json"));
+
+    synchronizer.start().unwrap();
+    simulator.run().await.unwrap();
+    tokio::time::timeout(Duration::from_secs(15), async {
+        loop {
+            if let Some(SyncEvent::SyncUpdated(_)) = rx.recv().await {
+                let view = server.live_view();
+                let finished = view
+                    .heats
+                    .iter()
+                    .filter(|heat| heat.lanes.iter().all(|lane| lane.finish_place.is_some()))
+                    .count();
+                if finished == heats {
+                    return;
+                }
+            }
+        }
+    })
+    .await
+    .expect("timed out waiting for the last heat to upload");
+    synchronizer.stop();
+
+    // Heats run faster than the watcher polls, so they arrive together
+    // rather than one upload per heat
+    assert!(server.uploads().len() <= heats);
+}
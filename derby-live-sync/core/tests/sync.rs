@@ -0,0 +1,268 @@
+mod common;
+
+use derby_live_core::events::SyncEvent;
+use derby_live_core::payload::RequestData;
+use derby_live_core::upload_queue::UploadQueue;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const EVENT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Waits for the first event that `matches` picks out, failing the test if
+/// it does not arrive in time.
+async fn wait_for<T>(
+    rx: &mut UnboundedReceiver<SyncEvent>,
+    mut matches: impl FnMut(SyncEvent) -> Option<T>,
+) -> T {
+    tokio::time::timeout(EVENT_TIMEOUT, async {
+        loop {
+            let event = rx.recv().await.expect("event channel closed");
+            if let Some(found) = matches(event) {
+                return found;
+            }
+        }
+    })
+    .await
+    .expect("timed out waiting for sync event")
+}
+
+async fn uploads(server: &MockServer) -> Vec<RequestData<'static>> {
+    server
+        .received_requests()
+        .await
+        .unwrap_or_default()
+        .iter()
+        .map(|request| request.body_json().expect("upload body is not RequestData"))
+        .collect()
+}
+
+async fn accepting_server() -> MockServer {
+    accepting_server_at("/api/data").await
+}
+
+async fn accepting_server_at(api_path: &str) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(api_path))
+        .and(header("x-api-key", common::API_KEY))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"status": "ok"})))
+        .mount(&server)
+        .await;
+    server
+}
+
+#[tokio::test]
+async fn first_sync_uploads_everything() {
+    let dir = tempfile::tempdir().unwrap();
+    let database_path = common::grandprix_database(dir.path());
+    let server = accepting_server().await;
+    let (synchronizer, mut rx) = common::synchronizer(
+        &database_path,
+        &server.uri(),
+        dir.path().join("upload_queue.json"),
+    );
+
+    synchronizer.start().unwrap();
+    wait_for(&mut rx, |event| match event {
+        SyncEvent::SyncUpdated(message) => Some(message),
+        _ => None,
+    })
+    .await;
+    synchronizer.stop();
+
+    let uploads = uploads(&server).await;
+    assert_eq!(uploads.len(), 1);
+    let upload = &uploads[0];
+    assert_eq!(upload.event_key, common::EVENT_KEY);
+    assert_eq!(upload.racers.len(), 6);
+    assert_eq!(upload.racer_heats.len(), 17);
+    assert_eq!(upload.structure.as_ref().unwrap().classes.len(), 2);
+    assert_eq!(upload.standings.as_ref().unwrap().len(), 6);
+}
+
+#[tokio::test]
+async fn uploads_under_the_path_the_site_is_mounted_at() {
+    let dir = tempfile::tempdir().unwrap();
+    let database_path = common::grandprix_database(dir.path());
+    let server = accepting_server_at("/derby/api/data").await;
+    let (synchronizer, mut rx) = common::synchronizer(
+        &database_path,
+        &format!("{}/derby/", server.uri()),
+        dir.path().join("upload_queue.json"),
+    );
+
+    synchronizer.start().unwrap();
+    wait_for(&mut rx, |event| match event {
+        SyncEvent::SyncUpdated(message) => Some(message),
+        _ => None,
+    })
+    .await;
+    synchronizer.stop();
+
+    assert_eq!(uploads(&server).await.len(), 1);
+}
+
+#[tokio::test]
+async fn later_syncs_upload_only_what_changed() {
+    let dir = tempfile::tempdir().unwrap();
+    let database_path = common::grandprix_database(dir.path());
+    let server = accepting_server().await;
+    let (synchronizer, mut rx) = common::synchronizer(
+        &database_path,
+        &server.uri(),
+        dir.path().join("upload_queue.json"),
+    );
+
+    synchronizer.start().unwrap();
+    wait_for(&mut rx, |event| match event {
+        SyncEvent::SyncUpdated(_) => Some(()),
+        _ => None,
+    })
+    .await;
+
+    let conn = rusqlite::Connection::open(&database_path).unwrap();
+    conn.execute(
+        "UPDATE RaceChart
+            SET FinishTime = 3.050, FinishPlace = 1, Completed = '2024-03-02 10:08:00'
+            WHERE ResultID = 13",
+        [],
+    )
+    .unwrap();
+    wait_for(&mut rx, |event| match event {
+        SyncEvent::SyncUpdated(_) => Some(()),
+        _ => None,
+    })
+    .await;
+    synchronizer.stop();
+
+    let uploads = uploads(&server).await;
+    assert_eq!(uploads.len(), 2);
+    let change = &uploads[1];
+    assert!(change.racers.is_empty());
+    assert_eq!(change.racer_heats.len(), 1);
+    assert_eq!(change.racer_heats[0].result_id, 13);
+    assert!(change.structure.is_none());
+    // Car 202 now has a time, so the Wolves leaderboard moved
+    assert!(change.standings.is_some());
+}
+
+#[tokio::test]
+async fn a_stopped_sync_watches_again_once_restarted() {
+    let dir = tempfile::tempdir().unwrap();
+    let database_path = common::grandprix_database(dir.path());
+    let server = accepting_server().await;
+    let (synchronizer, mut rx) = common::synchronizer(
+        &database_path,
+        &server.uri(),
+        dir.path().join("upload_queue.json"),
+    );
+    let conn = rusqlite::Connection::open(&database_path).unwrap();
+
+    synchronizer.start().unwrap();
+    wait_for(&mut rx, |event| match event {
+        SyncEvent::SyncUpdated(_) => Some(()),
+        _ => None,
+    })
+    .await;
+    synchronizer.stop();
+    // Long enough for the stopped sync's thread to notice and exit
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    conn.execute(
+        "UPDATE RaceChart SET FinishTime = 3.050, FinishPlace = 1 WHERE ResultID = 13",
+        [],
+    )
+    .unwrap();
+
+    synchronizer.start().unwrap();
+    wait_for(&mut rx, |event| match event {
+        SyncEvent::SyncUpdated(_) => Some(()),
+        _ => None,
+    })
+    .await;
+    conn.execute(
+        "UPDATE RaceChart SET FinishTime = 3.350, FinishPlace = 3 WHERE ResultID = 15",
+        [],
+    )
+    .unwrap();
+    wait_for(&mut rx, |event| match event {
+        SyncEvent::SyncUpdated(_) => Some(()),
+        _ => None,
+    })
+    .await;
+    synchronizer.stop();
+
+    let uploads = uploads(&server).await;
+    let last = uploads.last().unwrap();
+    assert_eq!(last.racer_heats.len(), 1);
+    assert_eq!(last.racer_heats[0].result_id, 15);
+}
+
+#[tokio::test]
+async fn rejected_api_key_stops_the_sync() {
+    let dir = tempfile::tempdir().unwrap();
+    let database_path = common::grandprix_database(dir.path());
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/data"))
+        .respond_with(
+            ResponseTemplate::new(401)
+                .set_body_json(serde_json::json!({"error": "Invalid API key"})),
+        )
+        .mount(&server)
+        .await;
+    let (synchronizer, mut rx) = common::synchronizer(
+        &database_path,
+        &server.uri(),
+        dir.path().join("upload_queue.json"),
+    );
+
+    synchronizer.start().unwrap();
+    let error = wait_for(&mut rx, |event| match event {
+        SyncEvent::SyncError(message) => Some(message),
+        _ => None,
+    })
+    .await;
+    wait_for(&mut rx, |event| match event {
+        SyncEvent::SyncStopped => Some(()),
+        _ => None,
+    })
+    .await;
+
+    assert!(error.contains("Invalid API key"), "{}", error);
+    assert!(!synchronizer.is_running());
+    // The upload is kept for when the key is fixed
+    assert_eq!(
+        UploadQueue::load_from(dir.path().join("upload_queue.json")).len(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn wrong_server_path_stops_the_sync() {
+    let dir = tempfile::tempdir().unwrap();
+    let database_path = common::grandprix_database(dir.path());
+    // Nothing is mounted, so every upload gets wiremock's 404
+    let server = MockServer::start().await;
+    let (synchronizer, mut rx) = common::synchronizer(
+        &database_path,
+        &format!("{}/wrong", server.uri()),
+        dir.path().join("upload_queue.json"),
+    );
+
+    synchronizer.start().unwrap();
+    let error = wait_for(&mut rx, |event| match event {
+        SyncEvent::SyncError(message) => Some(message),
+        _ => None,
+    })
+    .await;
+    wait_for(&mut rx, |event| match event {
+        SyncEvent::SyncStopped => Some(()),
+        _ => None,
+    })
+    .await;
+
+    assert!(error.contains("check the server URL"), "{}", error);
+    assert!(!synchronizer.is_running());
+}
@@ -1,9 +1,9 @@
 use crate::app_state::AppState;
 use crate::client_notify;
-use crate::settings::AppSettings;
+use derby_live_core::source;
 use log::info;
 use std::{
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
 };
 
@@ -14,17 +14,35 @@ pub async fn handle(
     info!(target: "command", "choose_database");
 
     let state = Arc::clone(&app_state);
+    // The dialog stays open while the profile could be switched elsewhere
+    let profile = match state.lock() {
+        Ok(state_locked) => state_locked.app_settings.active_profile.clone(),
+        Err(_) => {
+            info!(target: "command", "choose_database: failed to lock app_state");
+            return Err(());
+        }
+    };
 
     tauri::api::dialog::FileDialogBuilder::new().pick_file(move |file_path| {
+        if let Some(path) = &file_path {
+            if let Err(e) = check_database(Arc::clone(&state), path) {
+                info!(target: "command", "choose_database: rejected {:?}: {}", path, e);
+                client_notify::database_rejected(Arc::new(app_handle), e.to_string());
+                return;
+            }
+        }
+
         {
-            let chosen_file_path = assign_database_path(Arc::clone(&state), file_path);
+            let chosen_file_path = assign_database_path(Arc::clone(&state), &profile, file_path);
 
             client_notify::database_chosen(Arc::new(app_handle), chosen_file_path);
         }
 
         match state.lock() {
             Ok(state_locked) => {
-                tauri::async_runtime::spawn(AppSettings::write(state_locked.app_settings.clone()));
+                if let Err(e) = state_locked.app_settings.write() {
+                    info!(target: "command", "choose_database: failed to write settings: {}", e);
+                }
             }
             Err(_) => {
                 info!(target: "command", "choose_database: failed to lock app_state");
@@ -35,18 +53,35 @@ pub async fn handle(
     Ok(())
 }
 
-fn assign_database_path(app_state: Arc<Mutex<AppState>>, database_path: Option<PathBuf>) -> String {
+fn check_database(
+    app_state: Arc<Mutex<AppState>>,
+    database_path: &Path,
+) -> Result<source::DetectedSource, source::SourceError> {
+    let source_kind = match app_state.lock() {
+        Ok(state_locked) => state_locked.app_settings.source_kind,
+        Err(_) => Default::default(),
+    };
+
+    source::check(source_kind, database_path)
+}
+
+fn assign_database_path(
+    app_state: Arc<Mutex<AppState>>,
+    profile: &str,
+    database_path: Option<PathBuf>,
+) -> String {
     match app_state.lock() {
         Ok(mut state_locked) => {
+            let Some(profile) = state_locked.app_settings.profile_mut(profile) else {
+                return "".to_string();
+            };
             if let Some(path) = database_path {
-                state_locked
-                    .app_settings
-                    .update_database_path_if_exists(path);
+                profile.update_database_path_if_exists(path);
             } else {
-                state_locked.app_settings.database_path = None;
+                profile.database_path = None;
             }
 
-            state_locked.app_settings.current_database_path()
+            profile.current_database_path()
         }
         Err(_) => {
             info!(target: "command", "choose_database: failed to lock app_state");
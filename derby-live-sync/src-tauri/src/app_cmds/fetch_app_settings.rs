@@ -1,15 +1,42 @@
+use super::SyncPreferences;
 use crate::app_state::AppState;
-use crate::settings::AppSettings;
+use derby_live_core::settings::{AppSettings, Profile, ServerTarget, SettingsError};
 use log::info;
+use serde::Serialize;
 use std::sync::{Arc, Mutex};
 
-pub fn handle(app_state: tauri::State<'_, Arc<Mutex<AppState>>>) -> AppSettings {
+/// Settings as the settings window sees them: the active profile, the
+/// servers to pick from and how syncing behaves, whether an API key is saved, but never the key
+/// itself.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingsView {
+    #[serde(flatten)]
+    profile: Profile,
+    server_targets: Vec<ServerTarget>,
+    sync_preferences: SyncPreferences,
+    has_api_key: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    load_error: Option<SettingsError>,
+}
+
+pub fn handle(app_state: tauri::State<'_, Arc<Mutex<AppState>>>) -> SettingsView {
     info!(target: "fetch_app_settings", "handle");
-    let app_settings = match app_state.lock() {
-        Ok(state_locked) => state_locked.app_settings.clone(),
-        Err(_) => AppSettings::default(),
+    let (app_settings, load_error) = match app_state.lock() {
+        Ok(state_locked) => (
+            state_locked.app_settings.clone(),
+            state_locked.settings_error.clone(),
+        ),
+        Err(_) => (AppSettings::default(), None),
     };
     info!(target: "fetch_app_settings", "handle: app_settings {:?}", app_settings);
 
-    app_settings
+    let profile = app_settings.active().clone();
+    SettingsView {
+        has_api_key: profile.api_key.is_some(),
+        profile,
+        sync_preferences: SyncPreferences::from_settings(&app_settings),
+        server_targets: app_settings.server_targets,
+        load_error,
+    }
 }
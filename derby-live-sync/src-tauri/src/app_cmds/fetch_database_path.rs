@@ -1,5 +1,5 @@
 use crate::app_state::AppState;
-use crate::settings::AppSettings;
+use derby_live_core::settings::AppSettings;
 use log::info;
 use std::sync::{Arc, Mutex};
 
@@ -9,7 +9,7 @@ pub fn handle(app_state: tauri::State<'_, Arc<Mutex<AppState>>>) -> String {
         Ok(state_locked) => state_locked.app_settings.clone(),
         Err(_) => AppSettings::default(),
     };
-    let database_path = app_settings.current_database_path();
+    let database_path = app_settings.active().current_database_path();
     info!(target: "fetch_app_settings", "handle: database_path {:?}", database_path);
 
     database_path
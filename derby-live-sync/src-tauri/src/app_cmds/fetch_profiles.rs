@@ -0,0 +1,48 @@
+use crate::app_state::AppState;
+use log::info;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfilesView {
+    active: String,
+    profiles: Vec<ProfileStatus>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileStatus {
+    name: String,
+    syncing: bool,
+}
+
+/// Every profile's name and whether it is syncing, for the profile picker.
+pub fn handle(app_state: tauri::State<'_, Arc<Mutex<AppState>>>) -> ProfilesView {
+    info!(target: "fetch_profiles", "handle");
+    let state_locked = match app_state.lock() {
+        Ok(state_locked) => state_locked,
+        Err(_) => {
+            info!(target: "fetch_profiles", "handle: failed to lock app_state");
+            return ProfilesView::default();
+        }
+    };
+
+    let profiles = state_locked
+        .app_settings
+        .profiles
+        .iter()
+        .map(|profile| ProfileStatus {
+            name: profile.name.clone(),
+            syncing: state_locked
+                .synchronizers
+                .get(&profile.name)
+                .is_some_and(|synchronizer| synchronizer.is_running()),
+        })
+        .collect();
+
+    ProfilesView {
+        active: state_locked.app_settings.active_profile.clone(),
+        profiles,
+    }
+}
@@ -1,13 +1,17 @@
 mod choose_database;
 mod fetch_app_settings;
 mod fetch_database_path;
+mod fetch_profiles;
 mod save_settings;
 mod start_sync;
 mod stop_sync;
+mod switch_profile;
 
 pub use choose_database::handle as choose_database;
-pub use fetch_app_settings::handle as fetch_app_settings;
+pub use fetch_app_settings::{handle as fetch_app_settings, SettingsView};
 pub use fetch_database_path::handle as fetch_database_path;
-pub use save_settings::handle as save_settings;
+pub use fetch_profiles::{handle as fetch_profiles, ProfilesView};
+pub use save_settings::{handle as save_settings, SyncPreferences};
 pub use start_sync::handle as start_sync;
 pub use stop_sync::handle as stop_sync;
+pub use switch_profile::handle as switch_profile;
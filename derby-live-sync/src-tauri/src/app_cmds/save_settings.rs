@@ -1,40 +1,105 @@
 use crate::app_state::AppState;
-use crate::settings::AppSettings;
+use derby_live_core::settings::{self, AppSettings, ServerTarget, SettingsError};
+use derby_live_core::source::SourceKind;
+use derby_live_core::standings::ScoringMethod;
 use log::info;
+use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
 
+/// How every profile syncs, as the settings window edits it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncPreferences {
+    pub sync_debounce_ms: u64,
+    pub source_kind: SourceKind,
+    pub scoring_method: ScoringMethod,
+    pub warnings_block_sync: bool,
+}
+
+impl SyncPreferences {
+    pub fn from_settings(app_settings: &AppSettings) -> SyncPreferences {
+        SyncPreferences {
+            sync_debounce_ms: app_settings.sync_debounce_ms,
+            source_kind: app_settings.source_kind,
+            scoring_method: app_settings.scoring_method,
+            warnings_block_sync: app_settings.warnings_block_sync,
+        }
+    }
+
+    fn apply(self, app_settings: &mut AppSettings) {
+        app_settings.sync_debounce_ms = self.sync_debounce_ms;
+        app_settings.source_kind = self.source_kind;
+        app_settings.scoring_method = self.scoring_method;
+        app_settings.warnings_block_sync = self.warnings_block_sync;
+    }
+}
+
+/// Saves what the settings window edits: the active profile's keys and
+/// server, and the servers and sync preferences shared by every profile.
+/// Nothing is saved unless every field is valid, and the window is told
+/// which ones are not.
 pub async fn handle(
     api_key: String,
     event_key: String,
     server_url: String,
+    server_targets: Vec<ServerTarget>,
+    sync_preferences: SyncPreferences,
     app_state: tauri::State<'_, Arc<Mutex<AppState>>>,
-) -> Result<(), ()> {
+) -> Result<(), SettingsError> {
     info!(target: "save_settings", "handle");
     let state = Arc::clone(&app_state);
 
-    info!(target: "save_settings", "handled: api_key:{:?}, event_key:{:?}, server_url:{:?}", api_key, event_key, server_url);
-
-    {
-        match state.lock() {
-            Ok(mut state_locked) => {
-                state_locked.app_settings.api_key = Some(api_key.clone());
-                state_locked.app_settings.event_key = Some(event_key.clone());
-                state_locked.app_settings.server_url = server_url.clone();
-            }
-            Err(_) => {
-                info!(target: "save_settings", "handle: failed to lock app_state");
-            }
+    info!(target: "save_settings", "handled: event_key:{:?}, server_url:{:?}", event_key, server_url);
+
+    let mut state_locked = state.lock().map_err(|_| {
+        info!(target: "save_settings", "handle: failed to lock app_state");
+        SettingsError::Unwritable {
+            message: "the app is busy, try again".to_string(),
         }
+    })?;
+
+    let mut app_settings = state_locked.app_settings.clone();
+    let profile = app_settings.active_mut();
+    // Left blank to keep the key already saved
+    let api_key = api_key.trim();
+    let new_api_key = (!api_key.is_empty()).then(|| api_key.to_string());
+    if new_api_key.is_some() {
+        profile.api_key = new_api_key.clone();
     }
+    let event_key = event_key.trim();
+    profile.event_key = (!event_key.is_empty()).then(|| event_key.to_string());
+    profile.server_url = settings::normalize_server_url(&server_url);
+    app_settings.server_targets = server_targets
+        .into_iter()
+        .map(|target| ServerTarget {
+            name: target.name.trim().to_string(),
+            url: settings::normalize_server_url(&target.url),
+        })
+        .collect();
+    sync_preferences.apply(&mut app_settings);
 
-    match state.lock() {
-        Ok(state_locked) => {
-            tauri::async_runtime::spawn(AppSettings::write(state_locked.app_settings.clone()));
-        }
-        Err(_) => {
-            info!(target: "save_settings", "handle: failed to lock app_state");
+    if let Err(SettingsError::Invalid { mut fields }) = app_settings.validate() {
+        // The database is chosen from the main window, and may be on a
+        // drive that is not plugged in yet
+        fields.retain(|field| field.field != "databasePath");
+        if !fields.is_empty() {
+            info!(target: "save_settings", "handle: invalid fields {:?}", fields);
+            return Err(SettingsError::Invalid { fields });
         }
     }
 
+    if let Some(api_key) = new_api_key {
+        app_settings.active_mut().set_api_key(api_key)?;
+    }
+    app_settings.write().map_err(|e| {
+        info!(target: "save_settings", "handle: failed to write settings: {}", e);
+        SettingsError::Unwritable {
+            message: e.to_string(),
+        }
+    })?;
+
+    state_locked.app_settings = app_settings;
+    state_locked.settings_error = None;
+
     Ok(())
 }
@@ -1,10 +1,13 @@
 use crate::app_state::AppState;
 use crate::client_notify;
-use crate::settings::AppSettings;
-use crate::synchronize::{SyncCreationError, SyncState, Synchronizer};
+use derby_live_core::settings::AppSettings;
+use derby_live_core::synchronize::{SyncCreationError, SyncState, Synchronizer};
+use derby_live_core::upload_queue::UploadQueue;
 use log::info;
 use std::sync::{Arc, Mutex};
 
+/// Starts syncing the active profile. Profiles already syncing carry on, so
+/// switching profile and starting again syncs both.
 pub async fn handle(
     app_handle: tauri::AppHandle,
     app_state: tauri::State<'_, Arc<Mutex<AppState>>>,
@@ -12,53 +15,64 @@ pub async fn handle(
     info!(target: "start_sync", "handle");
     let state = Arc::clone(&app_state);
 
-    {
-        match state.lock() {
-            Ok(mut state_locked) => {
-                let app_settings = state_locked.app_settings.clone();
+    let synchronizer = match state.lock() {
+        Ok(mut state_locked) => {
+            let app_settings = state_locked.app_settings.clone();
+            let profile = app_settings.active_profile.clone();
 
-                if let Ok(synchronizer) = try_create_synchronizer(app_handle.clone(), app_settings)
-                {
-                    state_locked.synchronizer = Some(synchronizer);
-                } else {
+            if let Some(synchronizer) = state_locked.synchronizers.get(&profile) {
+                if synchronizer.is_running() {
+                    info!(target: "start_sync", "handle: {} is already syncing", profile);
+                    return Err(());
+                }
+            }
+
+            match try_create_synchronizer(app_handle.clone(), app_settings) {
+                Ok(synchronizer) => {
+                    state_locked
+                        .synchronizers
+                        .insert(profile, synchronizer.clone());
+                    synchronizer
+                }
+                Err(e) => {
                     client_notify::sync_error(
                         Arc::new(app_handle),
-                        "Failed to create synchronizer".to_string(),
+                        profile,
+                        format!("Failed to create synchronizer: {}", e),
                     );
                     return Err(());
                 }
             }
-            Err(_) => {
-                info!(target: "start_sync", "handle: failed to lock app_state");
-            }
-        }
-    }
-
-    match state.lock() {
-        Ok(state_locked) => {
-            if let Some(synchronizer) = state_locked.synchronizer.clone() {
-                synchronizer.start().map_err(|_| ())?;
-            }
         }
         Err(_) => {
             info!(target: "start_sync", "handle: failed to lock app_state");
+            return Err(());
         }
-    }
+    };
 
-    Ok(())
+    synchronizer.start().map_err(|_| ())
 }
 
 fn try_create_synchronizer(
     app_handle: tauri::AppHandle,
     app_settings: AppSettings,
 ) -> Result<Synchronizer, SyncCreationError> {
+    let sync_options = app_settings.sync_options();
+    let profile = app_settings.active().clone();
     let sync_state = SyncState::try_new(
-        app_handle.clone(),
-        app_settings.database_path,
-        app_settings.api_key,
-        app_settings.event_key,
-        Some(app_settings.server_url),
+        Arc::new(client_notify::AppEmitter::new(
+            app_handle,
+            profile.name.clone(),
+        )),
+        profile.database_path,
+        profile.api_key,
+        profile.event_key,
+        Some(profile.server_url),
+        sync_options,
     )?;
 
-    Ok(Synchronizer::new(sync_state))
+    Ok(Synchronizer::with_queue(
+        sync_state,
+        UploadQueue::for_profile(&profile.name),
+    ))
 }
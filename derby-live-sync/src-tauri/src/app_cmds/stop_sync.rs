@@ -2,11 +2,13 @@ use crate::app_state::AppState;
 use log::info;
 use std::sync::{Arc, Mutex};
 
+/// Stops syncing the active profile, leaving any others running.
 pub fn handle(app_state: tauri::State<'_, Arc<Mutex<AppState>>>) {
     info!(target: "stop_sync", "handle");
     match app_state.lock() {
         Ok(state_locked) => {
-            if let Some(synchronizer) = &state_locked.synchronizer {
+            let profile = &state_locked.app_settings.active_profile;
+            if let Some(synchronizer) = state_locked.synchronizers.get(profile) {
                 synchronizer.stop();
             }
         }
@@ -0,0 +1,47 @@
+use crate::app_state::AppState;
+use crate::client_notify;
+use derby_live_core::settings::{AppSettings, SettingsError};
+use log::info;
+use std::sync::{Arc, Mutex};
+
+/// Makes `name` the profile the windows show and edit. Any profile already
+/// syncing keeps syncing. With `create`, a new empty profile is added first.
+pub async fn handle(
+    name: String,
+    create: bool,
+    app_handle: tauri::AppHandle,
+    app_state: tauri::State<'_, Arc<Mutex<AppState>>>,
+) -> Result<(), SettingsError> {
+    info!(target: "switch_profile", "handle: name:{:?}, create:{}", name, create);
+    let state = Arc::clone(&app_state);
+
+    let mut state_locked = state.lock().map_err(|_| {
+        info!(target: "switch_profile", "handle: failed to lock app_state");
+        SettingsError::Unwritable {
+            message: "the app is busy, try again".to_string(),
+        }
+    })?;
+
+    let mut app_settings = state_locked.app_settings.clone();
+    if create {
+        app_settings.add_profile(&name)?;
+    } else {
+        app_settings.switch_profile(&name)?;
+    }
+    write(&app_settings)?;
+    let active_profile = app_settings.active_profile.clone();
+    state_locked.app_settings = app_settings;
+    drop(state_locked);
+
+    client_notify::profile_switched(Arc::new(app_handle), active_profile);
+    Ok(())
+}
+
+fn write(app_settings: &AppSettings) -> Result<(), SettingsError> {
+    app_settings.write().map_err(|e| {
+        info!(target: "switch_profile", "handle: failed to write settings: {}", e);
+        SettingsError::Unwritable {
+            message: e.to_string(),
+        }
+    })
+}
@@ -1,16 +1,12 @@
-use crate::settings::AppSettings;
-use crate::synchronize::Synchronizer;
+use derby_live_core::settings::{AppSettings, SettingsError};
+use derby_live_core::synchronize::Synchronizer;
+use std::collections::HashMap;
 
+#[derive(Default)]
 pub struct AppState {
     pub app_settings: AppSettings,
-    pub synchronizer: Option<Synchronizer>,
-}
-
-impl Default for AppState {
-    fn default() -> Self {
-        Self {
-            app_settings: Default::default(),
-            synchronizer: Default::default(),
-        }
-    }
+    /// Why settings.json could not be loaded at startup, until it is saved
+    pub settings_error: Option<SettingsError>,
+    /// One for each profile that has been synced, by profile name
+    pub synchronizers: HashMap<String, Synchronizer>,
 }
@@ -0,0 +1,425 @@
+use clap::Parser;
+use log::{info, LevelFilter};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::net::TcpListener;
+
+use crate::logger;
+use derby_live_core::config_dir;
+use derby_live_core::events::{EventSink, SyncEvent};
+use derby_live_core::mock_server::{self, MockServer};
+use derby_live_core::settings::{self, AppSettings, Profile, SettingsError};
+use derby_live_core::simulator::{Simulator, SimulatorOptions};
+use derby_live_core::source::SourceKind;
+use derby_live_core::standings::ScoringMethod;
+use derby_live_core::synchronize::{SyncState, Synchronizer};
+use derby_live_core::upload_queue::UploadQueue;
+use derby_live_core::validate::Severity;
+
+const API_KEY_ENV_VAR: &str = "DERBY_LIVE_API_KEY";
+
+/// Syncs a timing database to Derby Live. With no options the desktop app
+/// opens; with `--headless`, `--db` or any other sync option it syncs from
+/// the terminal instead, using settings.json for anything not given on the
+/// command line. Given
+/// `--profile` more than once, it syncs each of those profiles at once. With
+/// `--mock-server` it stands in for Derby Live itself, and with `--simulate`
+/// it stands in for the timer.
+#[derive(Debug, Parser)]
+#[command(name = "derby-live-sync", version)]
+pub struct Args {
+    /// Profile to sync headless instead of the one last picked in the app;
+    /// repeat to sync several at once
+    #[arg(long, value_name = "NAME")]
+    pub profile: Vec<String>,
+
+    /// Timing database to watch
+    #[arg(long)]
+    pub db: Option<PathBuf>,
+
+    /// Event key from the Derby Live event page
+    #[arg(long)]
+    pub event_key: Option<String>,
+
+    /// Derby Live server to upload to, by URL or by the name of a server
+    /// in settings such as "production" or "local mock"
+    #[arg(long)]
+    pub server: Option<String>,
+
+    /// API key for the Derby Live account. Read from DERBY_LIVE_API_KEY
+    /// when not given, so it stays out of the shell history
+    #[arg(long)]
+    pub api_key: Option<String>,
+
+    /// Where settings and the upload queue are kept, instead of the
+    /// platform config directory
+    #[arg(long, value_name = "DIR", env = config_dir::ENV_VAR)]
+    pub config_dir: Option<PathBuf>,
+
+    /// Sync without opening a window, using settings.json as is
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Run a local stand-in for Derby Live on this port instead of syncing,
+    /// accepting the API key and event key the sync would use, or printed
+    /// ones it makes up when there are none. The port is the one the "Local
+    /// mock" server in settings points at unless given.
+    #[arg(long, value_name = "PORT", num_args = 0..=1)]
+    pub mock_server: Option<Option<u16>>,
+
+    /// Create a GrandPrix database at this path and race it heat by heat,
+    /// for testing a sync pointed at the file
+    #[arg(long, value_name = "PATH")]
+    pub simulate: Option<PathBuf>,
+
+    /// Seconds between simulated heats
+    #[arg(long, value_name = "SECONDS", default_value_t = 10)]
+    pub heat_interval: u64,
+
+    /// Timing software that wrote the database: auto, grandPrix or
+    /// derbyNet
+    #[arg(long, value_name = "KIND")]
+    pub source: Option<SourceKind>,
+
+    /// How the leaderboard ranks racers: averageTime, points or
+    /// elimination
+    #[arg(long, value_name = "METHOD")]
+    pub scoring: Option<ScoringMethod>,
+
+    /// Hold back uploads while the data has warnings, not only errors
+    #[arg(long)]
+    pub warnings_block_sync: bool,
+
+    /// Milliseconds to wait after the database changes before reading it
+    #[arg(long, value_name = "MS")]
+    pub debounce_ms: Option<u64>,
+
+    /// Print the sync log as well as progress
+    #[arg(long, short)]
+    pub verbose: bool,
+}
+
+impl Args {
+    /// Every option here only means something without the window, so giving
+    /// any of them runs headless rather than leaving it silently unused.
+    /// DERBY_LIVE_API_KEY is not one of them, as it may be set for good.
+    pub fn is_headless(&self) -> bool {
+        self.headless
+            || self.db.is_some()
+            || self.mock_server.is_some()
+            || self.simulate.is_some()
+            || !self.profile.is_empty()
+            || self.event_key.is_some()
+            || self.server.is_some()
+            || self.api_key.is_some()
+            || self.source.is_some()
+            || self.scoring.is_some()
+            || self.warnings_block_sync
+            || self.debounce_ms.is_some()
+    }
+}
+
+/// Runs the synchronizer, mock server or simulator until it stops on a
+/// fatal error or the process is interrupted, returning the exit code.
+pub fn run(args: Args) -> i32 {
+    let level = if args.verbose {
+        LevelFilter::Info
+    } else {
+        LevelFilter::Warn
+    };
+    logger::init(level).expect("failed to initialize logger");
+    config_dir::migrate_from_cwd();
+
+    if let Some(path) = args.simulate {
+        return simulate(&path, Duration::from_secs(args.heat_interval));
+    }
+    let api_key = args
+        .api_key
+        .or_else(|| std::env::var(API_KEY_ENV_VAR).ok())
+        .filter(|api_key| !api_key.is_empty());
+
+    let mut app_settings = match AppSettings::load() {
+        Ok(app_settings) => app_settings,
+        Err(SettingsError::NotFound) => AppSettings::default(),
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+
+    let names = if args.profile.is_empty() {
+        vec![app_settings.active_profile.clone()]
+    } else {
+        args.profile
+    };
+    if let Some(name) = names
+        .iter()
+        .find(|name| app_settings.profile(name).is_none())
+    {
+        eprintln!("{}", SettingsError::UnknownProfile { name: name.clone() });
+        return 1;
+    }
+    let overridden =
+        args.db.is_some() || args.event_key.is_some() || args.server.is_some() || api_key.is_some();
+    if overridden && names.len() > 1 {
+        eprintln!("--db, --event-key, --server and --api-key apply to one profile at a time");
+        return 1;
+    }
+
+    if let Some(source_kind) = args.source {
+        app_settings.source_kind = source_kind;
+    }
+    if let Some(scoring_method) = args.scoring {
+        app_settings.scoring_method = scoring_method;
+    }
+    if args.warnings_block_sync {
+        app_settings.warnings_block_sync = true;
+    }
+    if let Some(debounce_ms) = args.debounce_ms {
+        app_settings.sync_debounce_ms = debounce_ms;
+    }
+
+    // The one the command line overrides, and the one checked along with
+    // the settings every profile shares
+    app_settings.active_profile = names[0].clone();
+
+    let server_url = args
+        .server
+        .map(|server| app_settings.resolve_server(&server));
+    let profile = app_settings
+        .profile_mut(&names[0])
+        .expect("profile was checked above");
+    if let Some(db) = args.db {
+        profile.database_path = Some(db);
+    }
+    if let Some(event_key) = args.event_key {
+        profile.event_key = Some(event_key);
+    }
+    if let Some(server_url) = server_url {
+        profile.server_url = server_url;
+    }
+    if let Some(api_key) = api_key {
+        profile.api_key = Some(api_key);
+    }
+
+    match args.mock_server {
+        Some(port) => serve_mock(port.unwrap_or(settings::LOCAL_MOCK_PORT), profile.clone()),
+        None => sync(&app_settings, &names),
+    }
+}
+
+fn sync(app_settings: &AppSettings, names: &[String]) -> i32 {
+    let profiles: Vec<&Profile> = names
+        .iter()
+        .filter_map(|name| app_settings.profile(name))
+        .collect();
+    // Output is only labelled when it could have come from more than one
+    let label = |profile: &Profile| {
+        if profiles.len() > 1 {
+            format!("[{}] ", profile.name)
+        } else {
+            String::new()
+        }
+    };
+
+    // The first profile is the active one here, so checking the settings as
+    // the window would covers it along with what every profile shares
+    let results = std::iter::once((profiles[0], app_settings.validate())).chain(
+        profiles[1..]
+            .iter()
+            .map(|profile| (*profile, profile.validate())),
+    );
+    let mut invalid = false;
+    for (profile, result) in results {
+        if let Err(SettingsError::Invalid { fields }) = result {
+            for field in fields {
+                eprintln!("{}{} {}", label(profile), field.field, field.message);
+            }
+            invalid = true;
+        }
+    }
+    if invalid {
+        return 1;
+    }
+
+    let mut synchronizers = Vec::new();
+    for profile in &profiles {
+        let sync_state = match SyncState::try_new(
+            Arc::new(ConsoleSink {
+                label: label(profile),
+            }),
+            profile.database_path.clone(),
+            profile.api_key.clone(),
+            profile.event_key.clone(),
+            Some(profile.server_url.clone()),
+            app_settings.sync_options(),
+        ) {
+            Ok(sync_state) => sync_state,
+            Err(e) => {
+                eprintln!("{}Failed to create synchronizer: {}", label(profile), e);
+                return 1;
+            }
+        };
+        synchronizers.push(Synchronizer::with_queue(
+            sync_state,
+            UploadQueue::for_profile(&profile.name),
+        ));
+    }
+
+    tauri::async_runtime::block_on(async {
+        for (profile, synchronizer) in profiles.iter().zip(&synchronizers) {
+            if let Err(e) = synchronizer.start() {
+                eprintln!("{}Failed to start sync: {}", label(profile), e);
+                synchronizers.iter().for_each(Synchronizer::stop);
+                return 1;
+            }
+        }
+
+        // Only fatal errors stop a running synchronizer on their own, and
+        // one stopping stops the rest so the exit code reports it
+        loop {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {
+                    info!(target: "cli", "run: interrupted");
+                    synchronizers.iter().for_each(Synchronizer::stop);
+                    return 0;
+                }
+                _ = tokio::time::sleep(Duration::from_secs(1)) => {
+                    if !synchronizers.iter().all(Synchronizer::is_running) {
+                        synchronizers.iter().for_each(Synchronizer::stop);
+                        return 1;
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Keys the profile has are used as they are, so a sync already set up
+/// works against the mock unchanged. Missing ones are made up in the shape
+/// Derby Live issues and printed, for entering in the settings window.
+fn serve_mock(port: u16, profile: Profile) -> i32 {
+    let api_key = profile.api_key.unwrap_or_else(|| {
+        let api_key = mock_server::generate_key(settings::API_KEY_LEN);
+        println!("API key: {}", api_key);
+        api_key
+    });
+    let event_key = profile.event_key.unwrap_or_else(|| {
+        let event_key = mock_server::generate_key(settings::EVENT_KEY_LEN);
+        println!("Event key: {}", event_key);
+        event_key
+    });
+    // Keys the sync would refuse could never reach the mock
+    let keys = Profile {
+        api_key: Some(api_key.clone()),
+        event_key: Some(event_key.clone()),
+        ..Profile::new(profile.name)
+    };
+    if let Err(SettingsError::Invalid { fields }) = keys.validate() {
+        for field in fields {
+            eprintln!("{} {}", field.field, field.message);
+        }
+        return 1;
+    }
+
+    tauri::async_runtime::block_on(async {
+        let listener = match TcpListener::bind(("127.0.0.1", port)).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                eprintln!("Failed to listen on port {}: {}", port, e);
+                return 1;
+            }
+        };
+        println!("Mock Derby Live server at http://localhost:{}", port);
+        println!("Heats page: http://localhost:{}/{}/heats", port, event_key);
+
+        let server = MockServer::new(api_key, event_key);
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => 0,
+            result = server.serve(listener) => {
+                if let Err(e) = result {
+                    eprintln!("Mock server stopped: {}", e);
+                }
+                1
+            }
+        }
+    })
+}
+
+fn simulate(path: &Path, heat_interval: Duration) -> i32 {
+    let options = SimulatorOptions {
+        heat_interval,
+        ..Default::default()
+    };
+    let simulator = match Simulator::create(path, &options) {
+        Ok(simulator) => simulator,
+        Err(e) => {
+            eprintln!("Failed to create {}: {}", path.display(), e);
+            return 1;
+        }
+    };
+    println!(
+        "Racing {} heats in {}, one every {}s",
+        simulator.remaining_heats(),
+        path.display(),
+        heat_interval.as_secs()
+    );
+
+    tauri::async_runtime::block_on(async {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => 0,
+            result = simulator.run() => match result {
+                Ok(()) => {
+                    println!("All heats run");
+                    0
+                }
+                Err(e) => {
+                    eprintln!("Simulator stopped: {}", e);
+                    1
+                }
+            }
+        }
+    })
+}
+
+/// Prints progress to stdout and problems to stderr, each line starting
+/// with `label`.
+struct ConsoleSink {
+    label: String,
+}
+
+impl EventSink for ConsoleSink {
+    fn emit(&self, event: SyncEvent) {
+        let label = &self.label;
+        match event {
+            SyncEvent::SyncStarted => println!("{}Sync started", label),
+            SyncEvent::SyncStopped => println!("{}Sync stopped", label),
+            SyncEvent::SyncError(message) => eprintln!("{}{}", label, message),
+            SyncEvent::SyncUpdated(message) => println!("{}{}", label, message),
+            SyncEvent::UploadQueueUpdated(depth) => {
+                println!("{}{} uploads waiting to send", label, depth)
+            }
+            SyncEvent::ValidationReported(report) => {
+                for issue in report.issues {
+                    match issue.severity {
+                        Severity::Warning => println!("{}Warning: {}", label, issue.message),
+                        Severity::Error => eprintln!("{}Error: {}", label, issue.message),
+                    }
+                }
+            }
+            SyncEvent::LaneStatsUpdated(track_health) => {
+                for lane in track_health.lanes.iter().filter(|lane| lane.drifting) {
+                    println!(
+                        "{}Lane {} is drifting ({:+.3}s)",
+                        label,
+                        lane.lane_number,
+                        lane.drift_seconds.unwrap_or_default()
+                    );
+                }
+            }
+        }
+    }
+}
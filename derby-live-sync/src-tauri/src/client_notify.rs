@@ -2,16 +2,71 @@ extern crate log;
 extern crate tauri;
 
 use log::info;
+use serde::Serialize;
 use std::sync::Arc;
 use tauri::{AppHandle, Manager};
 
+use derby_live_core::events::{EventSink, SyncEvent};
+use derby_live_core::lane_stats::TrackHealth;
+use derby_live_core::validate::ValidationReport;
+
+/// Subscribes the desktop window to one profile's sync engine events.
+pub struct AppEmitter {
+    app_handle: Arc<AppHandle>,
+    profile: String,
+}
+
+impl AppEmitter {
+    pub fn new(app_handle: AppHandle, profile: String) -> AppEmitter {
+        AppEmitter {
+            app_handle: Arc::new(app_handle),
+            profile,
+        }
+    }
+}
+
+impl EventSink for AppEmitter {
+    fn emit(&self, event: SyncEvent) {
+        let app_handle = self.app_handle.clone();
+        let profile = self.profile.clone();
+        match event {
+            SyncEvent::SyncStarted => sync_started(app_handle, profile),
+            SyncEvent::SyncStopped => sync_stopped(app_handle, profile),
+            SyncEvent::SyncError(message) => sync_error(app_handle, profile, message),
+            SyncEvent::SyncUpdated(message) => sync_updated(app_handle, profile, message),
+            SyncEvent::UploadQueueUpdated(depth) => {
+                upload_queue_updated(app_handle, profile, depth)
+            }
+            SyncEvent::ValidationReported(report) => {
+                validation_reported(app_handle, profile, report)
+            }
+            SyncEvent::LaneStatsUpdated(track_health) => {
+                lane_stats_updated(app_handle, profile, track_health)
+            }
+        }
+    }
+}
+
+/// Sync events name the profile they came from, since several can be
+/// syncing at once.
+#[derive(Debug, Clone, Serialize)]
+struct FromProfile<T> {
+    profile: String,
+    payload: T,
+}
+
 #[derive(Debug)]
 enum ServerEvent {
     DatabaseChosen(String),
-    SyncStarted,
-    SyncStopped,
-    SyncError(String),
-    SyncUpdated(String),
+    DatabaseRejected(String),
+    LaneStatsUpdated(String, TrackHealth),
+    ProfileSwitched(String),
+    SyncStarted(String),
+    SyncStopped(String),
+    SyncError(String, String),
+    SyncUpdated(String, String),
+    UploadQueueUpdated(String, usize),
+    ValidationReported(String, ValidationReport),
 }
 
 fn emit_all(app_handle: Arc<AppHandle>, server_event: ServerEvent) {
@@ -23,26 +78,93 @@ fn emit_all(app_handle: Arc<AppHandle>, server_event: ServerEvent) {
                 .emit_all("database_chosen", database_path)
                 .expect("failed to emit database_chosen");
         }
-        ServerEvent::SyncStarted => {
+        ServerEvent::DatabaseRejected(message) => {
+            app_handle
+                .emit_all("database_rejected", message)
+                .expect("failed to emit database_rejected");
+        }
+        ServerEvent::LaneStatsUpdated(profile, track_health) => {
+            app_handle
+                .emit_all(
+                    "lane_stats_updated",
+                    FromProfile {
+                        profile,
+                        payload: track_health,
+                    },
+                )
+                .expect("failed to emit lane_stats_updated");
+        }
+        ServerEvent::ProfileSwitched(profile) => {
             app_handle
-                .emit_all("sync_started", ())
+                .emit_all("profile_switched", profile)
+                .expect("failed to emit profile_switched");
+        }
+        ServerEvent::SyncStarted(profile) => {
+            app_handle
+                .emit_all(
+                    "sync_started",
+                    FromProfile {
+                        profile,
+                        payload: (),
+                    },
+                )
                 .expect("failed to emit sync_started");
         }
-        ServerEvent::SyncStopped => {
+        ServerEvent::SyncStopped(profile) => {
             app_handle
-                .emit_all("sync_stopped", ())
+                .emit_all(
+                    "sync_stopped",
+                    FromProfile {
+                        profile,
+                        payload: (),
+                    },
+                )
                 .expect("failed to emit sync_stopped");
         }
-        ServerEvent::SyncError(message) => {
+        ServerEvent::SyncError(profile, message) => {
             app_handle
-                .emit_all("sync_error", message)
+                .emit_all(
+                    "sync_error",
+                    FromProfile {
+                        profile,
+                        payload: message,
+                    },
+                )
                 .expect("failed to emit sync_err");
         }
-        ServerEvent::SyncUpdated(message) => {
+        ServerEvent::SyncUpdated(profile, message) => {
             app_handle
-                .emit_all("sync_updated", message)
+                .emit_all(
+                    "sync_updated",
+                    FromProfile {
+                        profile,
+                        payload: message,
+                    },
+                )
                 .expect("failed to emit sync_updated");
         }
+        ServerEvent::UploadQueueUpdated(profile, depth) => {
+            app_handle
+                .emit_all(
+                    "upload_queue_updated",
+                    FromProfile {
+                        profile,
+                        payload: depth,
+                    },
+                )
+                .expect("failed to emit upload_queue_updated");
+        }
+        ServerEvent::ValidationReported(profile, report) => {
+            app_handle
+                .emit_all(
+                    "validation_reported",
+                    FromProfile {
+                        profile,
+                        payload: report,
+                    },
+                )
+                .expect("failed to emit validation_reported");
+        }
     }
 }
 
@@ -50,18 +172,41 @@ pub fn database_chosen(app_handle: Arc<AppHandle>, database_path: String) {
     emit_all(app_handle, ServerEvent::DatabaseChosen(database_path));
 }
 
-pub fn sync_started(app_handle: Arc<AppHandle>) {
-    emit_all(app_handle, ServerEvent::SyncStarted);
+pub fn database_rejected(app_handle: Arc<AppHandle>, message: String) {
+    emit_all(app_handle, ServerEvent::DatabaseRejected(message));
+}
+
+pub fn lane_stats_updated(app_handle: Arc<AppHandle>, profile: String, track_health: TrackHealth) {
+    emit_all(
+        app_handle,
+        ServerEvent::LaneStatsUpdated(profile, track_health),
+    );
+}
+
+pub fn profile_switched(app_handle: Arc<AppHandle>, profile: String) {
+    emit_all(app_handle, ServerEvent::ProfileSwitched(profile));
+}
+
+pub fn sync_started(app_handle: Arc<AppHandle>, profile: String) {
+    emit_all(app_handle, ServerEvent::SyncStarted(profile));
+}
+
+pub fn sync_stopped(app_handle: Arc<AppHandle>, profile: String) {
+    emit_all(app_handle, ServerEvent::SyncStopped(profile));
+}
+
+pub fn sync_error(app_handle: Arc<AppHandle>, profile: String, message: String) {
+    emit_all(app_handle, ServerEvent::SyncError(profile, message));
 }
 
-pub fn sync_stopped(app_handle: Arc<AppHandle>) {
-    emit_all(app_handle, ServerEvent::SyncStopped);
+pub fn sync_updated(app_handle: Arc<AppHandle>, profile: String, message: String) {
+    emit_all(app_handle, ServerEvent::SyncUpdated(profile, message));
 }
 
-pub fn sync_error(app_handle: Arc<AppHandle>, message: String) {
-    emit_all(app_handle, ServerEvent::SyncError(message));
+pub fn upload_queue_updated(app_handle: Arc<AppHandle>, profile: String, depth: usize) {
+    emit_all(app_handle, ServerEvent::UploadQueueUpdated(profile, depth));
 }
 
-pub fn sync_updated(app_handle: Arc<AppHandle>, message: String) {
-    emit_all(app_handle, ServerEvent::SyncUpdated(message));
+pub fn validation_reported(app_handle: Arc<AppHandle>, profile: String, report: ValidationReport) {
+    emit_all(app_handle, ServerEvent::ValidationReported(profile, report));
 }
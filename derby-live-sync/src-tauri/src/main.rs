@@ -6,17 +6,17 @@ extern crate serde;
 extern crate tauri;
 
 mod app_state;
+mod cli;
 mod client_notify;
-mod database;
 mod logger;
-mod settings;
-mod synchronize;
 
 mod app_cmds;
 
 use app_state::AppState;
-use log::info;
-use settings::AppSettings;
+use clap::Parser;
+use derby_live_core::config_dir;
+use derby_live_core::settings::{AppSettings, ServerTarget, SettingsError};
+use log::{error, info, LevelFilter};
 use std::sync::{Arc, Mutex};
 use tauri::Manager;
 
@@ -30,7 +30,7 @@ async fn choose_database(
 }
 
 #[tauri::command]
-fn fetch_app_settings(app_state: tauri::State<'_, Arc<Mutex<AppState>>>) -> AppSettings {
+fn fetch_app_settings(app_state: tauri::State<'_, Arc<Mutex<AppState>>>) -> app_cmds::SettingsView {
     info!(target: "command", "fetch_app_settings");
     app_cmds::fetch_app_settings(app_state)
 }
@@ -41,15 +41,31 @@ fn fetch_database_path(app_state: tauri::State<'_, Arc<Mutex<AppState>>>) -> Str
     app_cmds::fetch_database_path(app_state)
 }
 
+#[tauri::command]
+fn fetch_profiles(app_state: tauri::State<'_, Arc<Mutex<AppState>>>) -> app_cmds::ProfilesView {
+    info!(target: "command", "fetch_profiles");
+    app_cmds::fetch_profiles(app_state)
+}
+
 #[tauri::command]
 async fn save_settings(
     api_key: String,
     event_key: String,
     server_url: String,
+    server_targets: Vec<ServerTarget>,
+    sync_preferences: app_cmds::SyncPreferences,
     app_state: tauri::State<'_, Arc<Mutex<AppState>>>,
-) -> Result<(), ()> {
+) -> Result<(), SettingsError> {
     info!(target: "command", "save_settings");
-    app_cmds::save_settings(api_key, event_key, server_url, app_state).await
+    app_cmds::save_settings(
+        api_key,
+        event_key,
+        server_url,
+        server_targets,
+        sync_preferences,
+        app_state,
+    )
+    .await
 }
 
 #[tauri::command]
@@ -67,22 +83,64 @@ fn stop_sync(app_state: tauri::State<'_, Arc<Mutex<AppState>>>) {
     app_cmds::stop_sync(app_state);
 }
 
+#[tauri::command]
+async fn switch_profile(
+    name: String,
+    create: bool,
+    app_handle: tauri::AppHandle,
+    app_state: tauri::State<'_, Arc<Mutex<AppState>>>,
+) -> Result<(), SettingsError> {
+    info!(target: "command", "switch_profile");
+    app_cmds::switch_profile(name, create, app_handle, app_state).await
+}
+
+/// Release builds on Windows have no console of their own, so anything
+/// printed when running headless would be lost. This borrows the console of
+/// whatever started the program; outside a console it fails quietly.
+#[cfg(all(windows, not(debug_assertions)))]
+fn attach_parent_console() {
+    use windows_sys::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(all(windows, not(debug_assertions))))]
+fn attach_parent_console() {}
+
 fn main() {
-    logger::init().expect("failed to initialize logger");
+    let args = cli::Args::try_parse().unwrap_or_else(|e| {
+        attach_parent_console();
+        e.exit()
+    });
+    if let Some(dir) = &args.config_dir {
+        config_dir::set_override(dir.clone());
+    }
+
+    if args.is_headless() {
+        attach_parent_console();
+        std::process::exit(cli::run(args));
+    }
+
+    logger::init(LevelFilter::Info).expect("failed to initialize logger");
+    config_dir::migrate_from_cwd();
 
     tauri::Builder::default()
         .manage::<Arc<Mutex<AppState>>>(Default::default())
         .setup(|app| {
+            let state: tauri::State<'_, Arc<Mutex<AppState>>> = app.state();
+            let mut state_locked = state.lock().unwrap();
             match AppSettings::load() {
                 Ok(app_settings) => {
-                    let state: tauri::State<'_, Arc<Mutex<AppState>>> = app.state();
-
-                    let mut state_locked = state.lock().unwrap();
                     state_locked.app_settings = app_settings;
                 }
-                Err(_) => {
+                Err(SettingsError::NotFound) => {
                     info!(target: "setup", "no settings.json found");
                 }
+                Err(e) => {
+                    error!(target: "setup", "{}", e);
+                    state_locked.settings_error = Some(e);
+                }
             }
 
             Ok(())
@@ -91,9 +149,11 @@ fn main() {
             choose_database,
             fetch_app_settings,
             fetch_database_path,
+            fetch_profiles,
             save_settings,
             start_sync,
             stop_sync,
+            switch_profile,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
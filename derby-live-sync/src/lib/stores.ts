@@ -1,6 +1,9 @@
 import { writable } from 'svelte/store';
 
 export const databasePath = writable<string>("");
-export const apiKey = writable<string>("");
 export const eventKey = writable<string>("");
-export const serverUrl = writable<string>("");
\ No newline at end of file
+export const serverUrl = writable<string>("");
+export const activeProfile = writable<string>("");
+
+// Sync events carry the profile they came from
+export type FromProfile<T> = { profile: string; payload: T };